@@ -1,6 +1,7 @@
 #! /bin/sh
 # Runs wallpapers random if the session is unlocked and if mpv is not playing.
 # Connects to the first session owned by the current user if DISPLAY is not set.
+# The daemon can do this itself with --interval instead.
 
 locks='i3lock swaylock hyprlock'
 
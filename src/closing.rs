@@ -24,8 +24,9 @@ pub fn init() {
         flag::register(*sig, CLOSED.clone()).unwrap();
     }
 
+    // The daemon reloads its config on SIGHUP instead.
     #[cfg(unix)]
-    {
+    if !matches!(crate::OPTIONS.cmd, crate::Command::Daemon { .. }) {
         flag::register(SIGHUP, CLOSED.clone()).unwrap();
     }
 }
@@ -6,7 +6,13 @@ use std::path::PathBuf;
 use std::string::ToString;
 use std::sync::{LazyLock, RwLock};
 
+use color_eyre::Result;
+use color_eyre::eyre::ensure;
+use glob::Pattern;
 use image::Rgba;
+use jiff::SignedDuration;
+use jiff::civil::Time;
+use serde::de::Error;
 use serde::ser::SerializeMap;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
@@ -35,12 +41,139 @@ pub struct Config {
 
     #[serde(default)]
     pub single_wallpaper: bool,
+
+    // Lists of connector names
+    #[serde(default)]
+    pub monitor_groups: Vec<Vec<String>>,
+
+    #[serde(default)]
+    pub extra_candidates: usize,
+
+    #[serde(default)]
+    pub max_upscale: Option<f64>,
+
+    // Overrides max_upscale by connector name
+    #[serde(default)]
+    pub monitor_max_upscale: BTreeMap<String, f64>,
+
+    #[serde(default)]
+    pub pools: Vec<Pool>,
+
+    #[serde(default)]
+    pub spans: Vec<Span>,
+
+    #[serde(default)]
+    pub directory_weights: BTreeMap<PathBuf, f64>,
+
+    #[serde(default)]
+    pub fair_directories: bool,
+
+    // Indexed by rating - 1
+    #[serde(default = "default_rating_weights")]
+    pub rating_weights: [f64; 5],
+
+    #[serde(default, deserialize_with = "deserialize_collections")]
+    pub collections: BTreeMap<String, Vec<Pattern>>,
+
+    #[serde(default)]
+    pub schedule: Vec<Schedule>,
+
+    #[serde(default = "default_history_length")]
+    pub history_length: usize,
+
+    // Number of recent sets of wallpapers whose series are avoided
+    #[serde(default)]
+    pub series_cooldown: usize,
+
+    #[cfg_attr(not(unix), allow(unused))]
+    #[serde(default)]
+    pub interval: Option<SignedDuration>,
+
+    // Defaults to a tenth of the interval
+    #[cfg_attr(not(unix), allow(unused))]
+    #[serde(default)]
+    pub interval_jitter: Option<SignedDuration>,
+
+    // Each monitor, or group of linked monitors, changes on its own timer
+    #[cfg_attr(not(unix), allow(unused))]
+    #[serde(default)]
+    pub independent_rotation: bool,
+
+    // Overrides interval by connector name, with independent rotation
+    #[cfg_attr(not(unix), allow(unused))]
+    #[serde(default)]
+    pub monitor_intervals: BTreeMap<String, SignedDuration>,
+
+    // Process names that pause timed rotations, like screen lockers
+    #[cfg_attr(not(unix), allow(unused))]
+    #[serde(default = "default_pause_processes")]
+    pub pause_processes: Vec<String>,
+
+    // Globs of mpv IPC sockets, timed rotations are paused while any of them are playing
+    #[cfg_attr(not(unix), allow(unused))]
+    #[serde(default = "default_mpv_sockets")]
+    pub mpv_sockets: Vec<String>,
+
+    // How long the daemon keeps trying to reconnect if the Wayland compositor restarts
+    #[cfg_attr(not(unix), allow(unused))]
+    #[serde(default = "default_wayland_reconnect")]
+    pub wayland_reconnect: SignedDuration,
 }
 
 const fn one() -> usize {
     1
 }
 
+const fn default_history_length() -> usize {
+    100
+}
+
+fn default_pause_processes() -> Vec<String> {
+    ["i3lock", "swaylock", "hyprlock"].map(String::from).into()
+}
+
+fn default_mpv_sockets() -> Vec<String> {
+    ["/tmp/mpvsocket", "/tmp/mpv-sockets/*"].map(String::from).into()
+}
+
+const fn default_wayland_reconnect() -> SignedDuration {
+    SignedDuration::from_secs(30)
+}
+
+const fn default_rating_weights() -> [f64; 5] {
+    [0.25, 0.5, 1.0, 2.0, 4.0]
+}
+
+// A subset of the originals directory used only for matching monitors.
+#[derive(Debug, Deserialize)]
+pub struct Pool {
+    #[serde(default)]
+    pub connector: Option<String>,
+
+    #[serde(default, deserialize_with = "deserialize_resolution")]
+    pub resolution: Option<(u32, u32)>,
+
+    #[serde(default, deserialize_with = "deserialize_aspect_ratio")]
+    pub aspect_ratio: Option<(u32, u32)>,
+
+    #[serde(deserialize_with = "deserialize_globs")]
+    pub include: Vec<Pattern>,
+}
+
+// Monitors that show one wallpaper stretched across all of them, as if they were one monitor.
+#[derive(Debug, Deserialize)]
+pub struct Span {
+    // Connector names
+    pub monitors: Vec<String>,
+
+    // Pixels hidden between adjacent monitors by their bezels.
+    #[serde(default)]
+    pub bezel: u32,
+
+    #[serde(deserialize_with = "deserialize_globs")]
+    pub include: Vec<Pattern>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Default)]
 pub struct ImageProperties {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -194,6 +327,120 @@ where
     if s.as_os_str().is_empty() { Ok(None) } else { Ok(Some(s.into())) }
 }
 
+// Restricts selection to a collection or set of globs during a time window and/or date range.
+#[derive(Debug, Deserialize)]
+pub struct Schedule {
+    #[serde(default, deserialize_with = "deserialize_time")]
+    pub start_time: Option<Time>,
+    #[serde(default, deserialize_with = "deserialize_time")]
+    pub end_time: Option<Time>,
+
+    // (month, day)
+    #[serde(default, deserialize_with = "deserialize_month_day")]
+    pub start_date: Option<(i8, i8)>,
+    #[serde(default, deserialize_with = "deserialize_month_day")]
+    pub end_date: Option<(i8, i8)>,
+
+    #[serde(default)]
+    pub collection: Option<String>,
+
+    #[serde(default, deserialize_with = "deserialize_globs")]
+    pub include: Vec<Pattern>,
+}
+
+fn deserialize_time<'de, D>(deserializer: D) -> Result<Option<Time>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+
+    s.parse()
+        .map(Some)
+        .map_err(|e| D::Error::custom(format!("Unable to parse time {s}: {e}")))
+}
+
+fn deserialize_month_day<'de, D>(deserializer: D) -> Result<Option<(i8, i8)>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+
+    s.split_once('-')
+        .and_then(|(m, d)| Some((m.trim().parse().ok()?, d.trim().parse().ok()?)))
+        .filter(|(m, d)| (1..=12).contains(m) && (1..=31).contains(d))
+        .map(Some)
+        .ok_or_else(|| D::Error::custom(format!("Unable to parse date {s}, expected MM-DD")))
+}
+
+fn deserialize_resolution<'de, D>(deserializer: D) -> Result<Option<(u32, u32)>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+
+    s.split_once('x')
+        .and_then(|(w, h)| Some((w.trim().parse().ok()?, h.trim().parse().ok()?)))
+        .filter(|(w, h)| *w > 0 && *h > 0)
+        .map(Some)
+        .ok_or_else(|| D::Error::custom(format!("Unable to parse resolution {s}")))
+}
+
+fn deserialize_aspect_ratio<'de, D>(deserializer: D) -> Result<Option<(u32, u32)>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+
+    s.split_once(':')
+        .and_then(|(w, h)| Some((w.trim().parse().ok()?, h.trim().parse().ok()?)))
+        .filter(|(w, h)| *w > 0 && *h > 0)
+        .map(|(w, h)| Some(reduce_ratio(w, h)))
+        .ok_or_else(|| D::Error::custom(format!("Unable to parse aspect ratio {s}")))
+}
+
+fn deserialize_globs<'de, D>(deserializer: D) -> Result<Vec<Pattern>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|s| Pattern::new(s).map_err(|e| D::Error::custom(format!("Invalid glob {s}: {e}"))))
+        .collect()
+}
+
+fn deserialize_collections<'de, D>(
+    deserializer: D,
+) -> Result<BTreeMap<String, Vec<Pattern>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    BTreeMap::<String, Vec<String>>::deserialize(deserializer)?
+        .into_iter()
+        .map(|(name, globs)| {
+            let patterns = globs
+                .iter()
+                .map(|s| {
+                    Pattern::new(s).map_err(|e| {
+                        D::Error::custom(format!("Invalid glob {s} in collection {name}: {e}"))
+                    })
+                })
+                .collect::<Result<_, _>>()?;
+            Ok((name, patterns))
+        })
+        .collect()
+}
+
+pub const fn reduce_ratio(w: u32, h: u32) -> (u32, u32) {
+    let (mut a, mut b) = (w, h);
+    while b != 0 {
+        let c = b;
+        b = a % b;
+        a = c;
+    }
+
+    (w / a, h / a)
+}
+
 fn zero_is_none<'de, D>(deserializer: D) -> Result<Option<i32>, D::Error>
 where
     D: Deserializer<'de>,
@@ -249,30 +496,111 @@ fn colour_to_string(c: Rgba<u8>) -> String {
     }
 }
 
-pub static CONFIG: LazyLock<Config> = LazyLock::new(|| {
-    let (config, _) =
-        awconf::load_config::<Config>("wallpapers", OPTIONS.awconf.as_ref(), None::<&str>)
-            .expect("Error loading config");
-    assert!(
+// Loads and validates the config, returning the path it was loaded from.
+fn load_config() -> Result<(Config, Option<PathBuf>)> {
+    let (config, path) =
+        awconf::load_config::<Config>("wallpapers", OPTIONS.awconf.as_ref(), None::<&str>)?;
+    ensure!(
         config.originals_directory.is_dir(),
         "Originals directory {:?} is not a directory",
         config.originals_directory
     );
 
     if !config.cache_directory.exists() {
-        create_dir(&config.cache_directory).unwrap();
+        create_dir(&config.cache_directory)?;
     }
 
-    assert!(
+    ensure!(
         config.cache_directory.is_dir(),
         "Cache directory {:?} is not a directory",
         config.cache_directory
     );
 
-    assert!(config.upscaling_jobs > 0, "Upscaling jobs cannot be 0");
+    ensure!(config.upscaling_jobs > 0, "Upscaling jobs cannot be 0");
+
+    for s in &config.schedule {
+        ensure!(
+            s.start_time.is_some() == s.end_time.is_some(),
+            "Schedules need both start_time and end_time or neither"
+        );
+        ensure!(
+            s.start_date.is_some() == s.end_date.is_some(),
+            "Schedules need both start_date and end_date or neither"
+        );
+        ensure!(
+            s.collection.is_some() == s.include.is_empty(),
+            "Schedules need exactly one of collection or include"
+        );
+        if let Some(c) = &s.collection {
+            ensure!(config.collections.contains_key(c), "Unknown collection {c:?} in schedule");
+        }
+    }
+
+    for limit in config.max_upscale.iter().chain(config.monitor_max_upscale.values()) {
+        ensure!(limit.is_finite() && *limit > 0.0, "Maximum upscale must be a positive number");
+    }
+
+    for interval in config.interval.iter().chain(config.monitor_intervals.values()) {
+        ensure!(interval.is_positive(), "Intervals must be positive");
+    }
+    if let Some(jitter) = config.interval_jitter {
+        ensure!(!jitter.is_negative(), "Interval jitter cannot be negative");
+    }
+    ensure!(!config.wayland_reconnect.is_negative(), "wayland_reconnect cannot be negative");
+
+    for s in &config.spans {
+        ensure!(s.monitors.len() >= 2, "Spans need at least two monitors");
+    }
+
+    for (dir, weight) in &config.directory_weights {
+        ensure!(
+            weight.is_finite() && *weight >= 0.0,
+            "Weight for directory {dir:?} must be a non-negative number"
+        );
+    }
+
+    for weight in &config.rating_weights {
+        ensure!(
+            weight.is_finite() && *weight >= 0.0,
+            "Rating weights must be non-negative numbers"
+        );
+    }
+
+    Ok((config, path))
+}
 
-    config
-});
+// The config can be replaced while running, so it's swapped out behind a lock. Old configs are
+// leaked since references to them can be held indefinitely, but reloads are rare.
+pub struct ConfigHandle(LazyLock<RwLock<(&'static Config, Option<PathBuf>)>>);
+
+impl Deref for ConfigHandle {
+    type Target = Config;
+
+    fn deref(&self) -> &Config {
+        self.0.read().unwrap().0
+    }
+}
+
+impl ConfigHandle {
+    // The file the config was loaded from, if any.
+    #[cfg_attr(not(unix), allow(unused))]
+    pub fn path(&self) -> Option<PathBuf> {
+        self.0.read().unwrap().1.clone()
+    }
+
+    // Replaces the config if the new one is valid.
+    #[cfg_attr(not(unix), allow(unused))]
+    pub fn reload(&self) -> Result<()> {
+        let (config, path) = load_config()?;
+        *self.0.write().unwrap() = (Box::leak(Box::new(config)), path);
+        Ok(())
+    }
+}
+
+pub static CONFIG: ConfigHandle = ConfigHandle(LazyLock::new(|| {
+    let (config, path) = load_config().expect("Error loading config");
+    RwLock::new((Box::leak(Box::new(config)), path))
+}));
 
 
 #[derive(Serialize, Deserialize)]
@@ -353,4 +681,17 @@ pub fn load_properties() -> Properties {
     Properties::deserialize(deserializer).expect("Unable to deserialize properties")
 }
 
+// Reloads properties on next use, unless the file is broken.
+#[cfg_attr(not(unix), allow(unused))]
+pub fn reload_properties() -> Result<()> {
+    let propfile = CONFIG.originals_directory.join(".properties.toml");
+    if propfile.is_file() {
+        let properties = read_to_string(&propfile)?;
+        Properties::deserialize(toml::Deserializer::parse(&properties)?)?;
+    }
+
+    *PROPERTIES.write().unwrap() = LazyLock::new(load_properties);
+    Ok(())
+}
+
 pub static PROPERTIES: RwLock<LazyLock<Properties>> = RwLock::new(LazyLock::new(load_properties));
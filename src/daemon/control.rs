@@ -0,0 +1,224 @@
+use std::env;
+use std::fs::{DirBuilder, remove_file};
+use std::io::ErrorKind;
+use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+use std::path::{PathBuf, absolute};
+use std::time::Duration;
+
+use clap::Subcommand;
+use color_eyre::Result;
+use color_eyre::eyre::{bail, eyre};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::timeout;
+
+// Requests are sent as a single line of JSON, and answered with a single line of JSON.
+#[derive(Debug, Clone, Subcommand, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+pub enum Request {
+    /// Change wallpapers on every monitor, or only on one.
+    Next {
+        /// Numbered from 0 as shown by list-monitors.
+        #[arg(short, long)]
+        monitor: Option<usize>,
+
+        /// Only select from this collection this time, instead of the daemon's collection.
+        #[arg(short, long)]
+        collection: Option<String>,
+    },
+    /// Show a specific wallpaper on every monitor, or only on one.
+    Show {
+        #[arg(value_parser)]
+        file: PathBuf,
+
+        /// Numbered from 0 as shown by list-monitors.
+        #[arg(short, long)]
+        monitor: Option<usize>,
+    },
+    /// Pin a wallpaper to a monitor and show it immediately.
+    Pin {
+        monitor: usize,
+
+        #[arg(value_parser)]
+        file: PathBuf,
+    },
+    /// Print the wallpapers currently shown and when they'll next change.
+    Status {
+        /// Print JSON for the current wallpapers instead of plain text.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Reload image properties and apply them to the current wallpapers.
+    Reload,
+    /// Shut the daemon down.
+    Quit,
+    /// Sent by a new daemon when it starts, so the old one knows to wait for it.
+    #[command(hide = true)]
+    Successor,
+    /// Sent by a new daemon once its wallpapers are set, so the old one can exit.
+    #[command(hide = true)]
+    Handover,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Response {
+    pub ok: bool,
+    #[serde(default)]
+    pub output: String,
+}
+
+impl From<Result<String>> for Response {
+    fn from(res: Result<String>) -> Self {
+        match res {
+            Ok(output) => Self { ok: true, output },
+            Err(e) => Self { ok: false, output: format!("{e}\n") },
+        }
+    }
+}
+
+// The control socket lives in the user's runtime directory, or in a private directory in /tmp when
+// there isn't one.
+pub fn socket_path() -> Result<PathBuf> {
+    if let Some(dir) = env::var_os("XDG_RUNTIME_DIR") {
+        return Ok(PathBuf::from(dir).join("wallpapers.sock"));
+    }
+
+    let uid = unsafe { libc::getuid() };
+    let dir = env::temp_dir().join(format!("wallpapers-{uid}"));
+    match DirBuilder::new().mode(0o700).create(&dir) {
+        Ok(()) => {}
+        Err(e) if e.kind() == ErrorKind::AlreadyExists => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    // Another user could have created it first.
+    let meta = dir.symlink_metadata()?;
+    if !meta.is_dir() || meta.uid() != uid || meta.mode() & 0o077 != 0 {
+        bail!("{dir:?} is not a private directory owned by this user");
+    }
+    Ok(dir.join("wallpapers.sock"))
+}
+
+// The daemon's end of the socket. Replaces any socket left by a daemon that has exited.
+pub struct Listener {
+    // Clients are accepted and read in the background, so waiting for requests is cancel safe.
+    requests: mpsc::Receiver<(UnixStream, Request)>,
+    accept: JoinHandle<()>,
+    path: PathBuf,
+    inode: u64,
+}
+
+impl Listener {
+    pub fn bind() -> Result<Self> {
+        let path = socket_path()?;
+        if path.exists() {
+            // Only sockets left behind by daemons that have exited are replaced.
+            if std::os::unix::net::UnixStream::connect(&path).is_ok() {
+                bail!("Another daemon is still listening on {path:?}");
+            }
+            remove_file(&path)?;
+        }
+
+        let listener = UnixListener::bind(&path)?;
+        let inode = path.metadata()?.ino();
+
+        let (sender, requests) = mpsc::channel(8);
+        let accept = tokio::spawn(accept(listener, sender));
+        Ok(Self { requests, accept, path, inode })
+    }
+
+    // Waits for the next client with a valid request.
+    pub async fn next(&mut self) -> (UnixStream, Request) {
+        self.requests.recv().await.expect("Control socket stopped accepting clients")
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        self.accept.abort();
+        // Leave the socket alone if a newer daemon has replaced it.
+        if self.path.metadata().is_ok_and(|m| m.ino() == self.inode) {
+            let _ignored = remove_file(&self.path);
+        }
+    }
+}
+
+// Broken clients are answered and skipped.
+async fn accept(listener: UnixListener, sender: mpsc::Sender<(UnixStream, Request)>) {
+    loop {
+        let mut stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                println!("Error accepting connection on control socket: {e}");
+                continue;
+            }
+        };
+
+        // A slow client shouldn't hold up the others.
+        let sender = sender.clone();
+        tokio::spawn(async move {
+            match timeout(Duration::from_secs(5), read_request(&mut stream)).await {
+                Ok(Ok(request)) => {
+                    let _ignored = sender.send((stream, request)).await;
+                }
+                Ok(Err(e)) => respond(stream, Err(e)).await,
+                Err(_) => respond(stream, Err(eyre!("Timed out reading request"))).await,
+            }
+        });
+    }
+}
+
+async fn read_request(stream: &mut UnixStream) -> Result<Request> {
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line).await?;
+    Ok(serde_json::from_str(&line)?)
+}
+
+pub async fn respond(mut stream: UnixStream, res: Result<String>) {
+    let mut line = serde_json::to_string(&Response::from(res)).expect("Impossible");
+    line.push('\n');
+    if let Err(e) = stream.write_all(line.as_bytes()).await {
+        println!("Error responding on control socket: {e}");
+    }
+}
+
+// Connects to a running daemon, if there is one.
+pub async fn connect() -> Option<UnixStream> {
+    UnixStream::connect(socket_path().ok()?).await.ok()
+}
+
+pub async fn request(mut stream: UnixStream, request: &Request) -> Result<String> {
+    let mut line = serde_json::to_string(request)?;
+    line.push('\n');
+    stream.write_all(line.as_bytes()).await?;
+
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line).await?;
+    let response: Response = serde_json::from_str(&line)?;
+
+    if !response.ok {
+        bail!("{}", response.output.trim_end());
+    }
+    Ok(response.output)
+}
+
+// The ctl subcommand.
+pub async fn run(mut request: Request) -> Result<()> {
+    // The daemon won't share our working directory.
+    if let Request::Show { file, .. } | Request::Pin { file, .. } = &mut request
+        && file.exists()
+    {
+        *file = absolute(&*file)?;
+    }
+
+    let path = socket_path()?;
+    let Ok(stream) = UnixStream::connect(&path).await else {
+        bail!("No daemon is listening on {path:?}");
+    };
+
+    print!("{}", self::request(stream, &request).await?);
+    Ok(())
+}
@@ -0,0 +1,631 @@
+use std::fmt::Write;
+use std::future::pending;
+use std::path::Path;
+use std::pin::pin;
+use std::process;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use color_eyre::Result;
+use color_eyre::eyre::{bail, eyre};
+use futures::StreamExt;
+use jiff::SignedDuration;
+use libc::{SIGHUP, SIGUSR1, SIGUSR2};
+use signal_hook::consts::TERM_SIGNALS;
+use signal_hook_tokio::Signals;
+use tokio::net::UnixStream;
+use tokio::select;
+use tokio::time::{self, Instant, Interval, MissedTickBehavior, sleep, timeout};
+
+use self::control::Request;
+use self::notify::Notifier;
+use self::prewarm::Prewarm;
+use self::timers::Timers;
+use self::watch::{Change, Watcher};
+use crate::config::{self, CONFIG};
+use crate::directories::ids::OriginalWallpaperID;
+use crate::directories::resolve_original;
+use crate::monitors::{self, Connection, Monitor, Update};
+use crate::processing::SMALL_POOLS;
+use crate::state::current::Current;
+use crate::wallpaper::clear_caches;
+use crate::{Drawer, SelectionOpts, random, selection, show, state};
+
+pub mod control;
+mod notify;
+mod pause;
+mod prewarm;
+mod timers;
+mod watch;
+
+// Processing uncached wallpapers can be slow, so a new daemon gets a while to set its own.
+const HANDOVER_TIMEOUT: Duration = Duration::from_secs(60);
+// Running alongside a previous daemon that won't hand over would leave two daemons fighting over
+// the wallpapers, so this one gives up instead.
+const HANDOVER_ATTEMPTS: usize = 2;
+// How long SIGUSR2 waits for a new daemon to start before exiting without one.
+const SUCCESSOR_TIMEOUT: Duration = Duration::from_secs(1);
+
+pub async fn run(
+    print: bool,
+    interval: Option<SignedDuration>,
+    restore: bool,
+    opts: &SelectionOpts,
+) {
+    // Prioritize a small footprint over completing things quickly
+    SMALL_POOLS.store(true, Ordering::Relaxed);
+
+    if let Err(e) = tokio_run(print, interval, restore, opts).await {
+        println!("Daemon exited with error {e}");
+        // So service managers can tell this apart from a clean shutdown.
+        process::exit(1);
+    }
+}
+
+async fn tokio_run(
+    print: bool,
+    interval: Option<SignedDuration>,
+    restore: bool,
+    opts: &SelectionOpts,
+) -> Result<()> {
+    // Fail now rather than on every rotation.
+    if let Some(collection) = &opts.collection
+        && !CONFIG.collections.contains_key(collection)
+    {
+        bail!("Unknown collection {collection:?}");
+    }
+    // Schedules would never change.
+    if opts.at.is_some() {
+        bail!("--at is only for testing schedules with random --dry-run");
+    }
+    if interval.is_some_and(|i| !i.is_positive()) {
+        bail!("Interval must be positive");
+    }
+
+    let mut signals = Signals::new(TERM_SIGNALS)?;
+    signals.handle().add_signal(SIGUSR1)?;
+    signals.handle().add_signal(SIGUSR2)?;
+    signals.handle().add_signal(SIGHUP)?;
+
+    // Any previous daemon waits for this one to take over, even if it's already been signalled.
+    announce().await;
+
+    let mut watcher = Watcher::new()
+        .inspect_err(|e| println!("Unable to watch config files for changes: {e}"))
+        .ok();
+
+    // Any previous daemon keeps the control socket until this one has set wallpapers.
+    let mut listener = None;
+    let mut started = false;
+    // A new daemon has started and will take over
+    let mut successor = false;
+
+    let notifier = Notifier::from_env();
+    let watchdog = notifier.as_ref().and_then(Notifier::start_watchdog);
+    // While waiting, the loop has to wake up regularly to keep the watchdog happy.
+    let mut alive = watchdog.as_ref().map(|w| {
+        let mut alive = time::interval(w.alive_interval());
+        alive.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        alive
+    });
+
+    let mut con = monitors::init();
+    let mut monitors = con.list_monitors().await?;
+    let mut action = if restore { Action::Resume } else { Action::Random(None, None) };
+    // The command line overrides the config, which can change.
+    let interval = || interval.or(CONFIG.interval);
+    let mut timers = Timers::new(interval(), &monitors);
+    // SIGHUP while setting wallpapers
+    let mut reload_pending = false;
+    // A client waiting for the action to finish
+    let mut reply: Option<UnixStream> = None;
+    // The next wallpapers for every monitor
+    let mut prewarm: Option<Prewarm> = None;
+
+    'outer: loop {
+        if let Some(w) = &watchdog {
+            w.busy(true);
+        }
+
+        let rotated = match &action {
+            Action::Random(only, _) => Some(*only),
+            Action::Resume => Some(None),
+            Action::Show(..) | Action::Restore | Action::Fill => None,
+        };
+        if rotated == Some(None) {
+            timers = Timers::new(interval(), &monitors);
+        }
+
+        if let Some(p) = &mut prewarm {
+            p.pause();
+        }
+
+        let mut failed = false;
+        {
+            let mut act = pin!(async {
+                // Only one thing is processed at a time.
+                let _processing = prewarm::exclusive().await;
+                let prepared = match action {
+                    Action::Random(None, None) => {
+                        prewarm.take().and_then(|p| p.take(&monitors, opts))
+                    }
+                    _ => None,
+                };
+                act(&mut con, monitors.clone(), print, opts, action, prepared).await
+            });
+
+            'inner: loop {
+                select! {
+                    res = &mut act => {
+                        if let Err(e) = &res {
+                            // There's nothing to fall back to on the first attempt, and any
+                            // previous daemon is still running.
+                            if !started {
+                                bail!("Unable to set the first wallpapers: {e}");
+                            }
+                            // Errors from random() can be ignored as temporary
+                            // If there's a problem with the connection, it should fail
+                            // in list_monitors() below.
+                            println!("Got unexpected error: {e}");
+                            failed = true;
+                        }
+                        if let Some(stream) = reply.take() {
+                            let res = res.and_then(|()| state::current::describe(false));
+                            control::respond(stream, res).await;
+                        }
+                        break 'inner;
+                    },
+                    sig = signals.next() => {
+                        match sig {
+                            Some(SIGUSR1) => {
+                                println!("Ignoring SIGUSR1 while setting wallpapers");
+                            },
+                            Some(SIGHUP) => {
+                                println!("Got SIGHUP, reloading after setting wallpapers");
+                                reload_pending = true;
+                            },
+                            Some(SIGUSR2) => {
+                                // Waiting for a new daemon can take as long as its processing.
+                                if let Some(w) = &watchdog {
+                                    w.busy(true);
+                                }
+                                hand_over(listener.take(), &mut signals, successor).await;
+                                break 'outer;
+                            },
+                            Some(sig) => {
+                                println!("Got signal {sig}, exiting cleanly");
+                                break 'outer;
+                            },
+                            None => unreachable!(),
+                        }
+                    },
+                }
+            }
+        }
+
+        if !started {
+            started = true;
+            listener = take_over().await?;
+            if let Some(n) = &notifier {
+                n.ready();
+            }
+        }
+        if let Some(n) = &notifier {
+            n.status();
+        }
+
+        if reload_pending {
+            reload(&[Change::Config, Change::Properties], watcher.as_mut());
+            reload_pending = false;
+        }
+
+        cleanup();
+
+        if failed && let Err(e) = con.list_monitors().await {
+            monitors = con.reconnect(e).await?;
+            println!("Reconnected, restoring wallpapers on {} monitors", monitors.len());
+            action = Action::Fill;
+            continue 'outer;
+        }
+
+        // Any rotation, including from SIGUSR1, restarts the timers.
+        match rotated {
+            Some(None) => timers.start_all(),
+            Some(Some(n)) => timers.restart_monitor(n),
+            None => {}
+        }
+
+        if rotated == Some(None) {
+            prewarm = Prewarm::new(&monitors, opts);
+        } else if let Some(p) = &mut prewarm {
+            p.resume();
+        }
+
+        if let Some(w) = &watchdog {
+            w.busy(false);
+        }
+
+        action = loop {
+            select! {
+                () = next_tick(alive.as_mut()) => {
+                    if let Some(w) = &watchdog {
+                        w.alive();
+                    }
+                }
+                sig = signals.next() => {
+                    match sig {
+                        Some(SIGUSR1) => break Action::Random(None, None),
+                        Some(SIGHUP) => {
+                            println!("Got SIGHUP, reloading");
+                            reload(&[Change::Config, Change::Properties], watcher.as_mut());
+                        },
+                        Some(SIGUSR2) => {
+                            if let Some(w) = &watchdog {
+                                w.busy(true);
+                            }
+                            hand_over(listener.take(), &mut signals, successor).await;
+                            break 'outer;
+                        },
+                        Some(sig) => {
+                            println!("Got signal {sig}, exiting cleanly");
+                            break 'outer;
+                        },
+                        None => unreachable!(),
+                    }
+                },
+                update = con.poll() => {
+                    match update {
+                        Update::Monitors(updated) => {
+                            monitors = updated;
+                            println!("Got updates to {} monitors", monitors.len());
+                            action = Action::Random(None, None);
+                        }
+                        Update::Disconnected(e) => {
+                            if let Some(w) = &watchdog {
+                                w.busy(true);
+                            }
+                            monitors = con.reconnect(e).await?;
+                            println!(
+                                "Reconnected, restoring wallpapers on {} monitors",
+                                monitors.len()
+                            );
+                            action = Action::Fill;
+                        }
+                    }
+                    continue 'outer
+                }
+                (timer, monitor) = timers.wait() => {
+                    let Some(reason) = pause::reason().await else {
+                        break Action::Random(monitor, None);
+                    };
+
+                    println!("Skipping timed rotation, {reason}");
+                    timers.restart(timer);
+                }
+                changes = next_changes(watcher.as_ref()) => {
+                    match changes {
+                        Ok(changes) => reload(&changes, watcher.as_mut()),
+                        Err(e) => {
+                            println!("Stopped watching config files for changes: {e}");
+                            watcher = None;
+                        }
+                    }
+                }
+                (stream, request) = next_request(listener.as_mut()) => {
+                    let res = match request {
+                        Request::Status { json } => status(json, timers.next_deadline()),
+                        Request::Quit => {
+                            control::respond(stream, Ok("Exiting\n".to_string())).await;
+                            println!("Got quit request, exiting cleanly");
+                            break 'outer;
+                        }
+                        Request::Successor => {
+                            successor = true;
+                            Ok(String::new())
+                        }
+                        Request::Handover => {
+                            // The new daemon only binds the socket once this one has let go.
+                            drop(listener.take());
+                            control::respond(stream, Ok(String::new())).await;
+                            println!("A new daemon has taken over, exiting cleanly");
+                            break 'outer;
+                        }
+                        request => match to_action(request, &monitors) {
+                            Ok(action) => {
+                                reply = Some(stream);
+                                break action;
+                            }
+                            Err(e) => Err(e),
+                        },
+                    };
+                    control::respond(stream, res).await;
+                }
+            }
+        };
+
+        // Reconnecting can take a while.
+        if let Some(w) = &watchdog {
+            w.busy(true);
+        }
+        // Any action sets every monitor, so it's enough to reconnect.
+        monitors = match con.list_monitors().await {
+            Ok(monitors) => monitors,
+            Err(e) => con.reconnect(e).await?,
+        };
+    }
+
+    if let Some(n) = &notifier {
+        n.stopping();
+    }
+    Ok(())
+}
+
+// What to do with the monitors at the start of each loop.
+enum Action {
+    // Random wallpapers on every monitor, or only on one, optionally from a different collection
+    // than the daemon's
+    Random(Option<usize>, Option<String>),
+    // A specific wallpaper on every monitor, or only on one
+    Show(OriginalWallpaperID, Option<usize>),
+    // The current wallpapers again, with any new properties
+    Restore,
+    // The wallpapers shown before the daemon started, where they're still processed, and random
+    // wallpapers on any other monitors
+    Resume,
+    // The current wallpapers again, and random wallpapers on any monitors without one, like new
+    // monitors found when reconnecting
+    Fill,
+}
+
+async fn act(
+    con: &mut Connection,
+    monitors: Vec<Monitor>,
+    print: bool,
+    opts: &SelectionOpts,
+    action: Action,
+    prepared: Option<Vec<Option<OriginalWallpaperID>>>,
+) -> Result<()> {
+    match action {
+        Action::Random(only, collection) => {
+            let unchanged = prepared.or_else(|| only.map(|n| others(n, &monitors)));
+            let opts = &SelectionOpts {
+                collection: collection.or_else(|| opts.collection.clone()),
+                ..opts.clone()
+            };
+            random(con, monitors, print, opts, Drawer::new(), unchanged).await
+        }
+        Action::Show(wid, Some(n)) => {
+            let mut selection = Current::load().selection(&monitors);
+            for i in selection::linked(n, &monitors) {
+                selection[i] = Some(wid.clone());
+            }
+            show(con, monitors, selection, print).await
+        }
+        Action::Show(wid, None) => {
+            let selection = vec![Some(wid); monitors.len()];
+            show(con, monitors, selection, print).await
+        }
+        Action::Restore => {
+            let selection = Current::load().selection(&monitors);
+            show(con, monitors, selection, print).await
+        }
+        Action::Resume => {
+            let restored = Current::load().restorable(&monitors);
+            random(con, monitors, print, opts, Drawer::new(), Some(restored)).await
+        }
+        Action::Fill => {
+            let current = Current::load().selection(&monitors);
+            random(con, monitors, print, opts, Drawer::new(), Some(current)).await
+        }
+    }
+}
+
+// The current wallpapers, except on monitor n and any monitors linked to it.
+fn others(n: usize, monitors: &[Monitor]) -> Vec<Option<OriginalWallpaperID>> {
+    let mut current = Current::load().selection(monitors);
+    for i in selection::linked(n, monitors) {
+        current[i] = None;
+    }
+    current
+}
+
+fn to_action(request: Request, monitors: &[Monitor]) -> Result<Action> {
+    let monitor = match &request {
+        Request::Next { monitor, .. } | Request::Show { monitor, .. } => *monitor,
+        Request::Pin { monitor, .. } => Some(*monitor),
+        _ => None,
+    };
+    if let Some(n) = monitor
+        && n >= monitors.len()
+    {
+        bail!("No monitor {n}, see list-monitors");
+    }
+
+    let resolve = |file: &Path| {
+        resolve_original(file)
+            .ok_or_else(|| eyre!("{file:?} is not a wallpaper in the originals directory"))
+    };
+
+    if let Request::Next { collection: Some(collection), .. } = &request
+        && !CONFIG.collections.contains_key(collection)
+    {
+        bail!("Unknown collection {collection:?}");
+    }
+
+    Ok(match request {
+        Request::Next { monitor, collection } => Action::Random(monitor, collection),
+        Request::Show { file, monitor } => Action::Show(resolve(&file)?, monitor),
+        Request::Pin { monitor, file } => {
+            resolve(&file)?;
+            state::pins::pin_to(monitors, monitor, &file)?;
+            Action::Random(Some(monitor), None)
+        }
+        Request::Reload => Action::Restore,
+        Request::Status { .. } | Request::Quit | Request::Successor | Request::Handover => {
+            unreachable!()
+        }
+    })
+}
+
+fn status(json: bool, deadline: Option<Instant>) -> Result<String> {
+    let mut out = state::current::describe(json)?;
+    if let Some(deadline) = deadline
+        && !json
+    {
+        let remaining = deadline.saturating_duration_since(Instant::now()).as_secs();
+        let remaining = SignedDuration::from_secs(remaining.try_into().unwrap_or(i64::MAX));
+        writeln!(out, "Next change in {remaining:#}")?;
+    }
+    Ok(out)
+}
+
+// Waits for a new daemon to set its wallpapers before exiting, so the monitors are never left
+// empty. Without a new daemon starting, this exits after a second.
+async fn hand_over(listener: Option<control::Listener>, signals: &mut Signals, successor: bool) {
+    let Some(mut listener) = listener else {
+        println!("Got SIGUSR2, exiting in one second");
+        sleep(SUCCESSOR_TIMEOUT).await;
+        return;
+    };
+
+    println!("Got SIGUSR2, waiting for a new daemon to take over");
+    let start = Instant::now();
+    let mut successor = successor;
+    loop {
+        let wait = if successor { HANDOVER_TIMEOUT } else { SUCCESSOR_TIMEOUT };
+
+        select! {
+            (stream, request) = listener.next() => match request {
+                Request::Successor => {
+                    successor = true;
+                    control::respond(stream, Ok(String::new())).await;
+                }
+                Request::Handover => {
+                    // The new daemon only binds the socket once this one has let go.
+                    drop(listener);
+                    control::respond(stream, Ok(String::new())).await;
+                    println!("A new daemon has taken over, exiting cleanly");
+                    return;
+                }
+                _ => control::respond(stream, Err(eyre!("Shutting down"))).await,
+            },
+            sig = signals.next() => match sig {
+                Some(sig) if TERM_SIGNALS.contains(&sig) => {
+                    println!("Got signal {sig}, exiting without waiting");
+                    return;
+                }
+                Some(_) => {}
+                None => unreachable!(),
+            },
+            () = time::sleep_until(start + wait) => {
+                if successor {
+                    println!(
+                        "No new daemon took over within {}s, exiting anyway",
+                        HANDOVER_TIMEOUT.as_secs()
+                    );
+                } else {
+                    println!("No new daemon started, exiting");
+                }
+                return;
+            }
+        }
+    }
+}
+
+// Lets any previous daemon know this one is starting.
+async fn announce() {
+    if let Some(stream) = control::connect().await {
+        let _ignored =
+            timeout(SUCCESSOR_TIMEOUT, control::request(stream, &Request::Successor)).await;
+    }
+}
+
+// Tells any previous daemon that this one has set its wallpapers, then takes over the control
+// socket. Fails if a previous daemon is still running after every attempt.
+async fn take_over() -> Result<Option<control::Listener>> {
+    let bind = || {
+        control::Listener::bind()
+            .inspect_err(|e| println!("Unable to listen on control socket: {e}"))
+            .ok()
+    };
+
+    for _ in 0..HANDOVER_ATTEMPTS {
+        let Some(stream) = control::connect().await else {
+            return Ok(bind());
+        };
+
+        match timeout(HANDOVER_TIMEOUT, control::request(stream, &Request::Handover)).await {
+            Ok(Ok(_)) => {
+                println!("Took over from the previous daemon");
+                return Ok(bind());
+            }
+            Ok(Err(e)) => println!("Previous daemon didn't hand over: {e}"),
+            Err(_) => println!("Timed out waiting for the previous daemon to hand over"),
+        }
+    }
+
+    bail!("The previous daemon is still running, stop it before starting a new one")
+}
+
+async fn next_request(listener: Option<&mut control::Listener>) -> (UnixStream, Request) {
+    match listener {
+        Some(listener) => listener.next().await,
+        None => pending().await,
+    }
+}
+
+async fn next_tick(interval: Option<&mut Interval>) {
+    match interval {
+        Some(interval) => drop(interval.tick().await),
+        None => pending().await,
+    }
+}
+
+async fn next_changes(watcher: Option<&Watcher>) -> Result<Vec<Change>> {
+    match watcher {
+        Some(watcher) => watcher.next().await,
+        None => pending().await,
+    }
+}
+
+// Changes are used from the next time wallpapers are set. Broken files are reported and the old
+// config or properties are kept. The next wallpapers are dropped if the config changes.
+// Any next wallpapers that were already selected are kept, they're checked against the new config
+// when they're used.
+fn reload(changes: &[Change], watcher: Option<&mut Watcher>) {
+    let mut reload_properties = changes.contains(&Change::Properties);
+
+    if changes.contains(&Change::Config) {
+        match CONFIG.reload() {
+            Ok(()) => {
+                println!("Reloaded config");
+                // The originals directory, and the properties inside it, may have changed.
+                reload_properties = true;
+                if let Some(watcher) = watcher
+                    && let Err(e) = watcher.watch()
+                {
+                    println!("Unable to watch config files for changes: {e}");
+                }
+            }
+            Err(e) => println!("Unable to reload config, keeping the old one: {e}"),
+        }
+    }
+
+    if reload_properties {
+        match config::reload_properties() {
+            Ok(()) => println!("Reloaded properties"),
+            Err(e) => println!("Unable to reload properties, keeping the old ones: {e}"),
+        }
+    }
+}
+
+// Reset state and drop as much memory as possible
+fn cleanup() {
+    if let Err(e) = config::reload_properties() {
+        println!("Unable to reload properties, keeping the old ones: {e}");
+    }
+    clear_caches();
+    unsafe {
+        libc::malloc_trim(0);
+    }
+}
@@ -0,0 +1,251 @@
+use std::ffi::OsStr;
+#[cfg(target_os = "linux")]
+use std::os::linux::net::SocketAddrExt;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use std::{env, io, thread};
+
+use crate::state::current::Current;
+
+// Longer than setting any reasonable set of wallpapers or waiting for a handover, so only a daemon
+// that's stuck stops pinging the watchdog and gets restarted.
+const MAX_BUSY: Duration = Duration::from_secs(600);
+
+// Readiness and status notifications for systemd, when running as a Type=notify service.
+pub struct Notifier {
+    socket: UnixDatagram,
+    addr: SocketAddr,
+    watchdog: Option<Duration>,
+}
+
+impl Notifier {
+    // Does nothing unless systemd, or something like it, has set NOTIFY_SOCKET.
+    pub fn from_env() -> Option<Self> {
+        let path = env::var_os("NOTIFY_SOCKET")?;
+        let mut notifier = Self::new(&path)
+            .inspect_err(|e| println!("Unable to use NOTIFY_SOCKET {path:?}: {e}"))
+            .ok()?;
+
+        // The watchdog may be meant for another process.
+        let for_us =
+            env::var("WATCHDOG_PID").map_or(true, |pid| pid.parse() == Ok(std::process::id()));
+        notifier.watchdog = env::var("WATCHDOG_USEC")
+            .ok()
+            .and_then(|usec| usec.parse().ok())
+            .filter(|usec| for_us && *usec > 0)
+            .map(Duration::from_micros);
+
+        Some(notifier)
+    }
+
+    fn new(path: &OsStr) -> io::Result<Self> {
+        let addr = match path.as_bytes().strip_prefix(b"@") {
+            #[cfg(target_os = "linux")]
+            Some(name) => SocketAddr::from_abstract_name(name)?,
+            _ => SocketAddr::from_pathname(path)?,
+        };
+
+        Ok(Self {
+            socket: UnixDatagram::unbound()?,
+            addr,
+            watchdog: None,
+        })
+    }
+
+    fn send(&self, message: &str) {
+        if let Err(e) = self.socket.send_to_addr(message.as_bytes(), &self.addr) {
+            println!("Unable to notify systemd: {e}");
+        }
+    }
+
+    pub fn ready(&self) {
+        self.send("READY=1");
+    }
+
+    pub fn stopping(&self) {
+        self.send("STOPPING=1");
+    }
+
+    // Summarizes the current wallpapers on one line.
+    pub fn status(&self) {
+        let current = Current::load();
+        let shown: Vec<_> = current
+            .monitors
+            .iter()
+            .map(|s| {
+                let connector = s.connector.as_deref().unwrap_or("unknown");
+                format!("{connector}: {}", s.original.slash_path().display())
+            })
+            .collect();
+
+        let status = if shown.is_empty() {
+            "No wallpapers set".to_string()
+        } else {
+            shown.join(", ")
+        };
+        // Newlines separate assignments.
+        self.send(&format!("STATUS={}", status.replace('\n', " ")));
+    }
+
+    pub fn watchdog(&self) {
+        self.send("WATCHDOG=1");
+    }
+
+    // Pings the watchdog from its own thread, if systemd expects it, since setting wallpapers
+    // keeps the daemon loop busy for as long as processing takes.
+    pub fn start_watchdog(&self) -> Option<Watchdog> {
+        self.start_watchdog_for(MAX_BUSY)
+    }
+
+    fn start_watchdog_for(&self, max_busy: Duration) -> Option<Watchdog> {
+        let timeout = self.watchdog?;
+        let notifier = Self {
+            socket: self
+                .socket
+                .try_clone()
+                .inspect_err(|e| println!("Unable to start the watchdog: {e}"))
+                .ok()?,
+            addr: self.addr.clone(),
+            watchdog: None,
+        };
+
+        let watchdog = Watchdog {
+            interval: timeout / 4,
+            alive: Arc::default(),
+            busy: Arc::default(),
+        };
+        let (interval, alive, busy) =
+            (watchdog.interval, watchdog.alive.clone(), watchdog.busy.clone());
+
+        thread::Builder::new()
+            .name("watchdog".to_string())
+            .spawn(move || {
+                loop {
+                    thread::sleep(interval);
+                    let busy = busy.lock().unwrap().is_some_and(|t| t.elapsed() < max_busy);
+                    if alive.swap(false, Ordering::Relaxed) || busy {
+                        notifier.watchdog();
+                    }
+                }
+            })
+            .inspect_err(|e| println!("Unable to start the watchdog: {e}"))
+            .ok()?;
+
+        Some(watchdog)
+    }
+}
+
+// Pings only continue while the daemon loop keeps reporting that it's alive, or for a limited time
+// while it's busy setting wallpapers.
+pub struct Watchdog {
+    interval: Duration,
+    alive: Arc<AtomicBool>,
+    // When the loop last started something that could take a while
+    busy: Arc<Mutex<Option<Instant>>>,
+}
+
+impl Watchdog {
+    // How often the loop should report that it's alive, often enough that no ping is missed.
+    pub fn alive_interval(&self) -> Duration {
+        self.interval / 2
+    }
+
+    pub fn alive(&self) {
+        self.alive.store(true, Ordering::Relaxed);
+    }
+
+    pub fn busy(&self, busy: bool) {
+        *self.busy.lock().unwrap() = busy.then(Instant::now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_notify_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notify");
+        let listener = UnixDatagram::bind(&path).unwrap();
+
+        let notifier = Notifier::new(path.as_os_str()).unwrap();
+        notifier.ready();
+        notifier.watchdog();
+        notifier.stopping();
+
+        let mut buf = [0; 64];
+        for expected in ["READY=1", "WATCHDOG=1", "STOPPING=1"] {
+            let n = listener.recv(&mut buf).unwrap();
+            assert_eq!(&buf[..n], expected.as_bytes());
+        }
+    }
+
+    #[test]
+    fn test_watchdog_thread() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notify");
+        let listener = UnixDatagram::bind(&path).unwrap();
+        listener.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+        let mut notifier = Notifier::new(path.as_os_str()).unwrap();
+        notifier.watchdog = Some(Duration::from_millis(40));
+        let watchdog = notifier.start_watchdog().unwrap();
+
+        // Nothing is sent until the loop is alive or busy.
+        listener.set_nonblocking(true).unwrap();
+        thread::sleep(Duration::from_millis(50));
+        let mut buf = [0; 64];
+        assert!(listener.recv(&mut buf).is_err());
+
+        listener.set_nonblocking(false).unwrap();
+        watchdog.busy(true);
+        let n = listener.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"WATCHDOG=1");
+    }
+
+    #[test]
+    fn test_watchdog_busy_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notify");
+        let listener = UnixDatagram::bind(&path).unwrap();
+
+        let mut notifier = Notifier::new(path.as_os_str()).unwrap();
+        notifier.watchdog = Some(Duration::from_millis(40));
+        let watchdog = notifier.start_watchdog_for(Duration::from_millis(100)).unwrap();
+        watchdog.busy(true);
+
+        // Pings stop once the loop has been busy for too long, as if it were stuck.
+        thread::sleep(Duration::from_millis(200));
+        listener.set_nonblocking(true).unwrap();
+        let mut buf = [0; 64];
+        while listener.recv(&mut buf).is_ok() {}
+        thread::sleep(Duration::from_millis(50));
+        assert!(listener.recv(&mut buf).is_err());
+
+        // Until it reports that it's alive again.
+        watchdog.alive();
+        listener.set_nonblocking(false).unwrap();
+        listener.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let n = listener.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"WATCHDOG=1");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_abstract_notify_socket() {
+        let name = format!("wallpapers-test-{}", std::process::id());
+        let listener =
+            UnixDatagram::bind_addr(&SocketAddr::from_abstract_name(&name).unwrap()).unwrap();
+
+        let notifier = Notifier::new(OsStr::new(&format!("@{name}"))).unwrap();
+        notifier.ready();
+
+        let mut buf = [0; 64];
+        let n = listener.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"READY=1");
+    }
+}
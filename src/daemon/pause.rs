@@ -0,0 +1,78 @@
+use std::fs::{read_dir, read_to_string};
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::time::Duration;
+
+use tokio::task::spawn_blocking;
+use tokio::time::timeout;
+
+use crate::config::CONFIG;
+
+// Scanning /proc and talking to mpv can be slow, so it's done off the runtime and given up on if
+// it takes too long.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Returns why timed rotations should be skipped right now, if they should be.
+pub async fn reason() -> Option<String> {
+    match timeout(CHECK_TIMEOUT, spawn_blocking(check)).await {
+        Ok(Ok(reason)) => reason,
+        Ok(Err(e)) => {
+            println!("Unexpected error checking whether to pause: {e}");
+            None
+        }
+        Err(_) => {
+            println!("Timed out checking whether to pause, rotating anyway");
+            None
+        }
+    }
+}
+
+fn check() -> Option<String> {
+    if let Some(process) = running_lock_process() {
+        return Some(format!("{process} is running"));
+    }
+
+    playing_mpv_socket().map(|sock| format!("mpv is playing on {sock}"))
+}
+
+fn running_lock_process() -> Option<String> {
+    if CONFIG.pause_processes.is_empty() {
+        return None;
+    }
+
+    read_dir("/proc")
+        .ok()?
+        .flatten()
+        .filter_map(|de| read_to_string(de.path().join("comm")).ok())
+        .map(|comm| comm.trim_end().to_string())
+        .find(|comm| CONFIG.pause_processes.contains(comm))
+}
+
+fn playing_mpv_socket() -> Option<String> {
+    CONFIG
+        .mpv_sockets
+        .iter()
+        .filter_map(|pattern| glob::glob(pattern).ok())
+        .flatten()
+        .flatten()
+        .map(|p| p.to_string_lossy().to_string())
+        .find(|sock| mpv_playing(sock).unwrap_or(false))
+}
+
+// Asks mpv over its IPC socket whether it is paused.
+fn mpv_playing(sock: &str) -> Option<bool> {
+    let mut stream = UnixStream::connect(sock).ok()?;
+    stream.set_read_timeout(Some(Duration::from_secs(1))).ok()?;
+    stream.set_write_timeout(Some(Duration::from_secs(1))).ok()?;
+    stream.write_all(b"{ \"command\": [\"get_property\", \"pause\"] }\n").ok()?;
+
+    // mpv can send events before the reply.
+    for line in BufReader::new(stream).lines().take(16) {
+        let reply: serde_json::Value = serde_json::from_str(&line.ok()?).ok()?;
+        if reply.get("event").is_none() {
+            let paused = reply.get("data")?.as_bool()?;
+            return Some(!paused);
+        }
+    }
+    None
+}
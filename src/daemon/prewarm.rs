@@ -0,0 +1,197 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, LazyLock, OnceLock};
+use std::thread;
+
+use tokio::runtime::Handle;
+use tokio::sync::{Mutex, MutexGuard};
+
+use crate::directories::ids::{OriginalWallpaperID, WallpaperID};
+use crate::monitors::Monitor;
+use crate::processing::lower_priority;
+use crate::state::bans::Bans;
+use crate::state::same_monitor;
+use crate::wallpaper::{Wallpaper, span};
+use crate::{Drawer, SelectionOpts, closing, make_tdir, select, selection};
+
+// Held by whatever is processing wallpapers, so only one set is processed at a time.
+static PROCESSING: Mutex<()> = Mutex::const_new(());
+
+// The next wallpapers, selected and processed on a low priority thread after a rotation so the
+// next rotation only has to set them.
+pub struct Prewarm {
+    monitors: Vec<Monitor>,
+    selection: Arc<OnceLock<Vec<Option<OriginalWallpaperID>>>>,
+    // Replaced every time processing is resumed, so an old thread can't be resumed by accident.
+    stop: Arc<AtomicBool>,
+}
+
+// Waits for any processing of the next wallpapers to reach a point where it can stop.
+pub async fn exclusive() -> MutexGuard<'static, ()> {
+    PROCESSING.lock().await
+}
+
+impl Prewarm {
+    // Selecting records the wallpapers as used, the same as if they'd been selected later, so
+    // the selection is kept for as long as it's usable.
+    pub fn new(monitors: &[Monitor], opts: &SelectionOpts) -> Option<Self> {
+        if monitors.is_empty() {
+            return None;
+        }
+
+        let prewarm = Self {
+            monitors: monitors.to_vec(),
+            selection: Arc::default(),
+            stop: Arc::default(),
+        };
+
+        let monitors = prewarm.monitors.clone();
+        let opts = opts.clone();
+        let selection = prewarm.selection.clone();
+        let stop = prewarm.stop.clone();
+        let runtime = Handle::current();
+
+        spawn(move || {
+            let _processing = PROCESSING.blocking_lock();
+
+            let mut drawer = Drawer::new();
+            let selected = runtime
+                .block_on(select(&monitors, &opts, &mut drawer, None))
+                .and_then(|s| drawer.finish().map(|()| s));
+            match selected {
+                Ok(Some(selected)) => {
+                    let selected = selection.get_or_init(|| selected);
+                    process(&monitors, selected, &stop);
+                }
+                Ok(None) => {}
+                Err(e) => println!("Unable to select the next wallpapers: {e}"),
+            }
+        });
+
+        Some(prewarm)
+    }
+
+    // Stops once the wallpaper currently being processed is done, without waiting for it.
+    pub fn pause(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    // Picks up where processing stopped, once nothing else is being processed.
+    pub fn resume(&mut self) {
+        let Some(selection) = self.selection.get().cloned() else {
+            return;
+        };
+
+        self.pause();
+        self.stop = Arc::default();
+
+        let monitors = self.monitors.clone();
+        let stop = self.stop.clone();
+        spawn(move || {
+            let _processing = PROCESSING.blocking_lock();
+            process(&monitors, &selection, &stop);
+        });
+    }
+
+    // The selected wallpapers for any monitors that are still connected, where they're still
+    // allowed. Anything left unprocessed is processed when they're set, and monitors without a
+    // wallpaper get a new one.
+    //
+    // Must be called while holding exclusive(), so selection has finished.
+    pub fn take(
+        mut self,
+        monitors: &[Monitor],
+        opts: &SelectionOpts,
+    ) -> Option<Vec<Option<OriginalWallpaperID>>> {
+        self.pause();
+        let prepared = self.selection.get()?;
+
+        // Bans, schedules, and collections may have changed since.
+        let bans = Bans::load();
+        let allowed = selection::filter(prepared.iter().flatten().cloned().collect(), opts)
+            .inspect_err(|e| println!("Unable to use the next wallpapers: {e}"))
+            .ok()?;
+        let usable = |wid: &OriginalWallpaperID| {
+            allowed.contains(wid)
+                && !bans.contains(wid.slash_path())
+                && wid.original_abs_path().is_file()
+        };
+
+        let mut selection: Vec<_> = monitors
+            .iter()
+            .enumerate()
+            .map(|(i, m)| {
+                let j = self
+                    .monitors
+                    .iter()
+                    .enumerate()
+                    .position(|(j, o)| same_monitor(j, &o.connector, i, m))?;
+                prepared[j].clone().filter(usable)
+            })
+            .collect();
+
+        // Groups and spans share one wallpaper, so any that are only partly kept are replaced.
+        for i in 0..monitors.len() {
+            let linked = selection::linked(i, monitors);
+            if linked.iter().any(|j| selection[*j] != selection[i]) {
+                for j in linked {
+                    selection[j] = None;
+                }
+            }
+        }
+
+        Some(selection)
+    }
+}
+
+impl Drop for Prewarm {
+    fn drop(&mut self) {
+        self.pause();
+    }
+}
+
+fn spawn(f: impl FnOnce() + Send + 'static) {
+    thread::Builder::new()
+        .name("prewarm".to_string())
+        .spawn(move || {
+            lower_priority();
+            f();
+        })
+        .expect("Unable to spawn prewarm thread");
+}
+
+fn process(monitors: &[Monitor], selection: &[Option<OriginalWallpaperID>], stop: &AtomicBool) {
+    let tdir = LazyLock::new(make_tdir as _);
+    let stopped = || stop.load(Ordering::Relaxed) || closing::closed();
+
+    let spans = span::active(selection, monitors);
+
+    // Merge any duplicate wallpapers, one at a time so pausing is quick.
+    let mut grouped: Vec<(&OriginalWallpaperID, Vec<Monitor>)> = Vec::new();
+    for (i, (wid, m)) in selection.iter().zip(monitors).enumerate() {
+        let Some(wid) = wid else {
+            continue;
+        };
+        if spans.iter().any(|(_, l)| l.contains(i)) {
+            continue;
+        }
+
+        match grouped.iter_mut().find(|(w, _)| *w == wid) {
+            Some((_, ms)) => ms.push(m.clone()),
+            None => grouped.push((wid, vec![m.clone()])),
+        }
+    }
+
+    for (wid, ms) in &grouped {
+        if stopped() {
+            return;
+        }
+        Wallpaper::new(*wid, ms, &tdir).in_background().process(true);
+    }
+
+    for (wid, l) in &spans {
+        if stopped() {
+            return;
+        }
+        span::process(wid, l, &tdir, true);
+    }
+}
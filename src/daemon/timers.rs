@@ -0,0 +1,110 @@
+use std::future::pending;
+use std::time::Duration;
+
+use jiff::SignedDuration;
+use rand::Rng;
+use tokio::time::{Instant, sleep_until};
+
+use crate::config::CONFIG;
+use crate::monitors::Monitor;
+use crate::selection;
+
+// Timed rotations. Normally one timer changes every monitor at once, but with independent
+// rotation each monitor, or group of linked monitors, has its own.
+pub struct Timers(Vec<Timer>);
+
+struct Timer {
+    // None for every monitor
+    monitors: Option<Vec<usize>>,
+    interval: SignedDuration,
+    deadline: Option<Instant>,
+}
+
+impl Timers {
+    // Timers aren't running until they're started.
+    pub fn new(interval: Option<SignedDuration>, monitors: &[Monitor]) -> Self {
+        if !CONFIG.independent_rotation {
+            let timer = interval.map(|interval| Timer { monitors: None, interval, deadline: None });
+            return Self(timer.into_iter().collect());
+        }
+
+        let mut units: Vec<Vec<usize>> = Vec::new();
+        for i in 0..monitors.len() {
+            if !units.iter().any(|u| u.contains(&i)) {
+                units.push(selection::linked(i, monitors));
+            }
+        }
+
+        let timers = units
+            .into_iter()
+            .filter_map(|u| {
+                let interval = monitors[u[0]]
+                    .connector
+                    .as_ref()
+                    .and_then(|c| CONFIG.monitor_intervals.get(c))
+                    .copied()
+                    .or(interval)?;
+                Some(Timer { monitors: Some(u), interval, deadline: None })
+            })
+            .collect();
+        Self(timers)
+    }
+
+    // Starts every timer, staggered evenly across their intervals so monitors don't all change at
+    // once.
+    pub fn start_all(&mut self) {
+        let n = self.0.len();
+        for (k, t) in self.0.iter_mut().enumerate() {
+            let early = t.interval.as_secs_f64() * (n - 1 - k) as f64 / n as f64;
+            t.deadline = Some(next_rotation(t.interval) - Duration::from_secs_f64(early));
+        }
+    }
+
+    // Restarts the timer for a single monitor after it changed on its own.
+    pub fn restart_monitor(&mut self, monitor: usize) {
+        if let Some(t) = self
+            .0
+            .iter_mut()
+            .find(|t| t.monitors.as_ref().is_some_and(|u| u.contains(&monitor)))
+        {
+            t.deadline = Some(next_rotation(t.interval));
+        }
+    }
+
+    pub fn restart(&mut self, timer: usize) {
+        let t = &mut self.0[timer];
+        t.deadline = Some(next_rotation(t.interval));
+    }
+
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.0.iter().filter_map(|t| t.deadline).min()
+    }
+
+    // Waits for the next timer, returning it and the monitor it changes, if it doesn't change
+    // all of them.
+    pub async fn wait(&self) -> (usize, Option<usize>) {
+        let next = self
+            .0
+            .iter()
+            .enumerate()
+            .filter_map(|(i, t)| Some((i, t.deadline?)))
+            .min_by_key(|(_, deadline)| *deadline);
+
+        let Some((i, deadline)) = next else {
+            return pending().await;
+        };
+
+        sleep_until(deadline).await;
+        (i, self.0[i].monitors.as_ref().map(|u| u[0]))
+    }
+}
+
+// The interval is randomly lengthened or shortened by up to the jitter, so rotations don't line up
+// with other periodic tasks.
+fn next_rotation(interval: SignedDuration) -> Instant {
+    let interval = interval.as_secs_f64();
+    let jitter = CONFIG.interval_jitter.map_or(interval / 10.0, |j| j.as_secs_f64());
+    let offset = if jitter > 0.0 { rand::rng().random_range(-jitter..=jitter) } else { 0.0 };
+
+    Instant::now() + Duration::from_secs_f64((interval + offset).max(1.0))
+}
@@ -0,0 +1,104 @@
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::os::fd::{AsFd, AsRawFd, RawFd};
+use std::path::absolute;
+
+use color_eyre::Result;
+use nix::sys::inotify::{AddWatchFlags, InitFlags, Inotify, WatchDescriptor};
+use tokio::io::unix::AsyncFd;
+
+use crate::config::CONFIG;
+
+// Editors often replace files instead of writing them in place, so the directories are watched.
+const FLAGS: AddWatchFlags = AddWatchFlags::IN_CLOSE_WRITE.union(AddWatchFlags::IN_MOVED_TO);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    Config,
+    Properties,
+}
+
+struct Fd(Inotify);
+
+impl AsRawFd for Fd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_fd().as_raw_fd()
+    }
+}
+
+// Watches the config and properties files for changes.
+pub struct Watcher {
+    fd: AsyncFd<Fd>,
+    config: Option<(WatchDescriptor, OsString)>,
+    properties: Option<WatchDescriptor>,
+}
+
+impl Watcher {
+    pub fn new() -> Result<Self> {
+        let inotify = Inotify::init(InitFlags::IN_NONBLOCK | InitFlags::IN_CLOEXEC)?;
+        let mut watcher = Self {
+            fd: AsyncFd::new(Fd(inotify))?,
+            config: None,
+            properties: None,
+        };
+        watcher.watch()?;
+        Ok(watcher)
+    }
+
+    // Watches the current files, which can move when the config is reloaded.
+    pub fn watch(&mut self) -> Result<()> {
+        let inotify = &self.fd.get_ref().0;
+
+        // Both files can be in the same directory and share one watch, so this can fail.
+        for wd in [self.config.take().map(|c| c.0), self.properties.take()].into_iter().flatten() {
+            let _ignored = inotify.rm_watch(wd);
+        }
+
+        if let Some(path) = CONFIG.path() {
+            let path = absolute(path)?;
+            if let (Some(dir), Some(name)) = (path.parent(), path.file_name()) {
+                self.config = Some((inotify.add_watch(dir, FLAGS)?, name.to_os_string()));
+            }
+        }
+
+        self.properties = Some(inotify.add_watch(&CONFIG.originals_directory, FLAGS)?);
+        Ok(())
+    }
+
+    pub async fn next(&self) -> Result<Vec<Change>> {
+        loop {
+            let mut guard = self.fd.readable().await?;
+            let Ok(events) =
+                guard.try_io(|fd| fd.get_ref().0.read_events().map_err(io::Error::from))
+            else {
+                continue;
+            };
+
+            let mut changes = Vec::new();
+            for e in events? {
+                let name = e.name.as_deref();
+                let change = if self
+                    .config
+                    .as_ref()
+                    .is_some_and(|(wd, n)| *wd == e.wd && name == Some(n.as_os_str()))
+                {
+                    Change::Config
+                } else if self.properties == Some(e.wd)
+                    && name == Some(OsStr::new(".properties.toml"))
+                {
+                    Change::Properties
+                } else {
+                    continue;
+                };
+
+                if !changes.contains(&change) {
+                    changes.push(change);
+                }
+            }
+
+            if !changes.is_empty() {
+                return Ok(changes);
+            }
+        }
+    }
+}
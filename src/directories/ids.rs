@@ -29,7 +29,7 @@ pub trait WallpaperID: Send + Sync + std::fmt::Debug {
 pub struct OriginalWallpaperID(PathBuf);
 
 impl OriginalWallpaperID {
-    pub(super) fn from_rel_path<P: AsRef<Path>>(p: P) -> Self {
+    pub(crate) fn from_rel_path<P: AsRef<Path>>(p: P) -> Self {
         Self(slash_from_relative(p))
     }
 
@@ -2,7 +2,7 @@ use std::cmp::max;
 use std::ffi::{OsStr, OsString};
 use std::fs;
 use std::io::Error;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::LazyLock;
 
 use regex::Regex;
@@ -10,6 +10,7 @@ use walkdir::{DirEntry, WalkDir};
 
 use self::ids::OriginalWallpaperID;
 use crate::config::CONFIG;
+use crate::state::bans::Bans;
 
 pub mod ids;
 
@@ -23,8 +24,9 @@ pub fn valid_extension(ext: &OsStr) -> bool {
     EXTENSIONS.iter().any(|e| ext.eq_ignore_ascii_case(OsStr::new(e)))
 }
 
-// Gets all the originals as forward slash separated relative paths
+// Gets all the originals as forward slash separated relative paths, skipping banned files
 pub fn get_all_originals() -> Result<Vec<OriginalWallpaperID>, Error> {
+    let bans = Bans::load();
     let walk = WalkDir::new(&CONFIG.originals_directory)
         .into_iter()
         .collect::<Result<Vec<_>, _>>()?;
@@ -40,9 +42,30 @@ pub fn get_all_originals() -> Result<Vec<OriginalWallpaperID>, Error> {
                     .expect("File in originals directory did not have correct path prefix."),
             )
         })
+        .filter(|wid| !bans.contains(wid.slash_path()))
         .collect())
 }
 
+// Resolves a path, either absolute, relative to the current directory, or relative to the
+// originals directory, to an existing original.
+pub fn resolve_original(p: &Path) -> Option<OriginalWallpaperID> {
+    let originals = CONFIG.originals_directory.canonicalize().ok()?;
+
+    [p.to_path_buf(), CONFIG.originals_directory.join(p)]
+        .into_iter()
+        .filter_map(|p| p.canonicalize().ok())
+        .filter(|p| p.is_file() && p.extension().is_some_and(valid_extension))
+        .find_map(|p| p.strip_prefix(&originals).ok().map(OriginalWallpaperID::from_rel_path))
+}
+
+// Numbered files in the same directory with the same prefix, like artist_01.png and
+// artist_02.png, form a series. Returns the directory joined with the prefix.
+pub fn series(wid: &OriginalWallpaperID) -> Option<PathBuf> {
+    let slash = wid.slash_path();
+    let c = FILE_REGEX.captures(slash.file_name()?.to_str()?)?;
+    Some(slash.parent()?.join(&c[1]))
+}
+
 // Returns (prefix, next_number, max_digits)
 // Only returns if the directory is empty or contains all files matching the same prefix
 pub fn next_original_in_dir(abs_dir: &Path) -> Option<(OsString, usize, usize)> {
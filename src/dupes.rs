@@ -0,0 +1,112 @@
+use std::path::Path;
+
+use color_eyre::Result;
+
+use crate::directories::get_all_originals;
+use crate::directories::ids::{OriginalWallpaperID, WallpaperID};
+use crate::state::hashes::Hashes;
+use crate::state::resolutions::Resolutions;
+
+// Images whose hashes differ in at most this many bits are treated as duplicates.
+pub const DEFAULT_DISTANCE: u32 = 4;
+
+// A difference hash: one bit for each pair of horizontally adjacent pixels in a 9x8 grayscale
+// thumbnail. Survives resizing and re-encoding.
+pub fn dhash(p: &Path) -> Option<u64> {
+    let small = image::open(p).ok()?.thumbnail_exact(9, 8).into_luma8();
+
+    let mut hash = 0;
+    for y in 0..8 {
+        for x in 0..8 {
+            let brighter = small.get_pixel(x, y)[0] > small.get_pixel(x + 1, y)[0];
+            hash = hash << 1 | u64::from(brighter);
+        }
+    }
+    Some(hash)
+}
+
+const fn similar(a: u64, b: u64, distance: u32) -> bool {
+    (a ^ b).count_ones() <= distance
+}
+
+fn find(parents: &mut [usize], mut i: usize) -> usize {
+    while parents[i] != i {
+        parents[i] = parents[parents[i]];
+        i = parents[i];
+    }
+    i
+}
+
+pub fn run(distance: u32) -> Result<()> {
+    let wallpapers = get_all_originals()?;
+
+    let mut hashes = Hashes::load(&wallpapers)?;
+    hashes.prune(&wallpapers)?;
+    let resolutions = Resolutions::load(&wallpapers, true)?;
+
+    let hashed: Vec<_> = wallpapers.iter().filter_map(|w| Some((w, hashes.get(w)?))).collect();
+
+    // Union-find, so chains of similar images end up in one cluster.
+    let mut parents: Vec<_> = (0..hashed.len()).collect();
+    for i in 0..hashed.len() {
+        for j in i + 1..hashed.len() {
+            if similar(hashed[i].1, hashed[j].1, distance) {
+                let (a, b) = (find(&mut parents, i), find(&mut parents, j));
+                parents[a] = b;
+            }
+        }
+    }
+
+    let mut clusters: Vec<Vec<&OriginalWallpaperID>> = vec![Vec::new(); hashed.len()];
+    for (i, (w, _)) in hashed.iter().enumerate() {
+        let root = find(&mut parents, i);
+        clusters[root].push(w);
+    }
+    clusters.retain(|c| c.len() > 1);
+
+    if clusters.is_empty() {
+        println!("No duplicates found");
+        return Ok(());
+    }
+
+    for cluster in clusters {
+        println!("Possible duplicates:");
+        for w in cluster {
+            let res = resolutions
+                .get(w)
+                .map_or_else(|| "?x?".to_string(), |r| format!("{}x{}", r.w, r.h));
+            let size = w.original_abs_path().metadata().map_or(0, |m| m.len());
+            println!("  {:?} {res} {:.1} MiB", w.slash_path(), size as f64 / (1024.0 * 1024.0));
+        }
+    }
+    Ok(())
+}
+
+// Warns if a file about to be installed looks like one already in the library.
+pub fn warn_duplicates(file: &Path) {
+    let Some(hash) = dhash(file) else {
+        return;
+    };
+
+    let wallpapers = match get_all_originals() {
+        Ok(w) => w,
+        Err(e) => {
+            println!("Unable to check for duplicates: {e}");
+            return;
+        }
+    };
+
+    let hashes = match Hashes::load(&wallpapers) {
+        Ok(h) => h,
+        Err(e) => {
+            println!("Unable to check for duplicates: {e}");
+            return;
+        }
+    };
+
+    for w in &wallpapers {
+        if hashes.get(w).is_some_and(|h| similar(h, hash, DEFAULT_DISTANCE)) {
+            println!("Warning: {file:?} looks like a duplicate of {:?}", w.original_abs_path());
+        }
+    }
+}
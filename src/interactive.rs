@@ -28,7 +28,7 @@ use crate::directories::{
 #[cfg(feature = "opencl")]
 use crate::processing::resample::OPENCL_QUEUE;
 use crate::wallpaper::{OPTIMISTIC_CACHE, Wallpaper};
-use crate::{closing, make_tdir, monitors};
+use crate::{closing, dupes, make_tdir, monitors};
 
 #[derive(Debug)]
 enum Command {
@@ -195,8 +195,13 @@ pub async fn run(starting_path: &Path) -> Result<()> {
                      return Ok(());
                 }
             },
-            res = con.poll() => {
-                res?;
+            update = con.poll() => {
+                #[cfg(unix)]
+                if let monitors::Update::Disconnected(e) = update {
+                    con.reconnect(e).await?;
+                }
+                #[cfg(windows)]
+                update?;
                 // Just ignore this for now, some monitors needed updates but the user is
                 // interacting with the application.
                 continue;
@@ -408,6 +413,8 @@ fn install(rel: PathBuf, original: &Path) -> Option<PathBuf> {
         return None;
     }
 
+    dupes::warn_duplicates(original);
+
     // We already know the originals_directory must exist, and new_path must have a parent
     if let Err(e) = create_dir_all(dest.parent().unwrap()) {
         println!("Error creating directories: {e}");
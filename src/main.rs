@@ -6,8 +6,8 @@
 static GLOBAL: tikv_jemallocator::Jemalloc = tikv_jemallocator::Jemalloc;
 
 
-use std::collections::HashSet;
-use std::fs::{remove_dir, remove_file};
+use std::collections::{HashMap, HashSet};
+use std::fs::{copy, create_dir, read_dir, remove_dir, remove_file};
 use std::num::NonZeroUsize;
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicUsize, Ordering};
@@ -18,20 +18,25 @@ use std::{process, thread};
 use aw_shuffle::AwShuffler;
 use aw_shuffle::persistent::rocksdb::Shuffler;
 use aw_shuffle::persistent::{Options, PersistentShuffler};
-use clap::Parser;
+use clap::{Args, Parser};
 use color_eyre::Result;
 use config::PROPERTIES;
 use crossbeam_utils::thread::scope;
-use directories::ids::WallpaperID;
+use directories::ids::{OriginalWallpaperID, WallpaperID};
+use jiff::SignedDuration;
+use jiff::civil::DateTime;
 use lru::LruCache;
 use monitors::Monitor;
 #[cfg(feature = "opencl")]
 use processing::resample::{OPENCL_QUEUE, print_gpus};
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rand::seq::IndexedRandom;
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 use tempfile::TempDir;
 use tokio::time::sleep;
 use walkdir::{DirEntry, WalkDir};
-use wallpaper::OPTIMISTIC_CACHE;
+use wallpaper::{OPTIMISTIC_CACHE, span};
 #[cfg(any(not(unix), feature = "x11"))]
 use {
     config::{ImageProperties, string_to_colour},
@@ -43,6 +48,12 @@ use crate::config::CONFIG;
 use crate::directories::get_all_originals;
 use crate::monitors::Connection;
 use crate::processing::SMALL_POOLS;
+use crate::state::bans::Bans;
+use crate::state::current::{Current, Shown};
+use crate::state::history::History;
+use crate::state::pins::Pins;
+use crate::state::ratings::Ratings;
+use crate::state::resolutions::Resolutions;
 use crate::wallpaper::Wallpaper;
 
 pub(crate) mod closing;
@@ -50,9 +61,12 @@ mod config;
 #[cfg(unix)]
 mod daemon;
 mod directories;
+mod dupes;
 mod interactive;
 pub(crate) mod monitors;
 pub(crate) mod processing;
+mod selection;
+mod state;
 mod wallpaper;
 
 #[derive(Debug, Parser)]
@@ -74,9 +88,22 @@ enum Command {
     /// Display a random wallpaper on each monitor.
     Random {
         /// Print selected wallpapers to stdout.
-        /// Does not work on wayland since this is not the daemon process.
+        /// On Wayland this only works if the daemon is listening on its control socket.
         #[arg(long)]
         print: bool,
+
+        /// Print the wallpapers that would be selected for each monitor without processing or
+        /// setting them, and without recording them as used.
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Select uniformly using this seed instead of favouring less recently selected
+        /// wallpapers, so the same seed always selects the same wallpapers.
+        #[arg(long, requires = "dry_run")]
+        seed: Option<u64>,
+
+        #[command(flatten)]
+        opts: SelectionOpts,
     },
     /// Run as a pseudo-daemon, listening for updates on SIGUSR1.
     #[cfg(unix)]
@@ -84,6 +111,24 @@ enum Command {
         /// Print selected wallpapers to stdout.
         #[arg(long)]
         print: bool,
+
+        /// Change wallpapers on this interval, such as 30m or 1h. Overrides the config.
+        #[arg(long)]
+        interval: Option<SignedDuration>,
+
+        /// Start with the wallpapers that were shown before, if they're still processed, instead
+        /// of new ones. Monitors without one get a random wallpaper.
+        #[arg(long)]
+        restore: bool,
+
+        #[command(flatten)]
+        opts: SelectionOpts,
+    },
+    /// Send a command to a running daemon.
+    #[cfg(unix)]
+    Ctl {
+        #[command(subcommand)]
+        request: daemon::control::Request,
     },
     /// Prepopulate the cache of stale files and remove stale files.
     Sync {
@@ -142,11 +187,84 @@ enum Command {
         #[arg(value_parser)]
         file: PathBuf,
     },
+    /// Permanently exclude a wallpaper from selection without deleting it.
+    Ban {
+        #[arg(value_parser)]
+        file: PathBuf,
+    },
+    /// Allow a previously banned wallpaper to be selected again.
+    Unban {
+        #[arg(value_parser)]
+        file: PathBuf,
+    },
+    /// Always show this wallpaper on a monitor, numbered from 0 as shown by list-monitors, while
+    /// the others keep changing.
+    Pin {
+        monitor: usize,
+
+        #[arg(value_parser)]
+        file: PathBuf,
+    },
+    /// Let a pinned monitor change wallpapers again.
+    Unpin {
+        monitor: usize,
+    },
+    /// Rate the wallpapers currently shown from 1 to 5. Higher rated wallpapers are selected more
+    /// often.
+    Rate {
+        #[arg(value_parser = clap::value_parser!(u8).range(1..=5))]
+        rating: u8,
+
+        /// Only rate the wallpaper on this monitor, numbered from 0 as shown by list-monitors.
+        #[arg(short, long)]
+        monitor: Option<usize>,
+    },
+    /// Print the wallpapers currently shown on each monitor.
+    Current {
+        /// Print JSON instead of plain text.
+        #[arg(long)]
+        json: bool,
+    },
+    /// List previously shown wallpapers, newest first.
+    History,
+    /// Restore the previously shown wallpapers.
+    Previous {
+        /// Only restore the previous wallpaper on this monitor, numbered from 0 as shown by
+        /// list-monitors.
+        #[arg(short, long)]
+        monitor: Option<usize>,
+    },
+    /// Clear the ratings of the wallpapers currently shown.
+    Unrate {
+        /// Only clear the rating of the wallpaper on this monitor.
+        #[arg(short, long)]
+        monitor: Option<usize>,
+    },
+    /// Find visually similar wallpapers, so only the best copy can be kept.
+    Dupes {
+        /// How many bits the perceptual hashes of two wallpapers may differ by. Higher values find
+        /// more duplicates but also more false positives.
+        #[arg(short, long, default_value_t = dupes::DEFAULT_DISTANCE)]
+        distance: u32,
+    },
     ListMonitors,
     #[cfg(feature = "opencl")]
     ShowGpus,
 }
 
+#[derive(Debug, Clone, Args)]
+pub struct SelectionOpts {
+    /// Only select wallpapers from this collection, as defined in the config.
+    /// Overrides any schedules.
+    #[arg(long)]
+    collection: Option<String>,
+
+    /// Select as if it were this local time instead of now, for testing schedules.
+    /// Example: 2024-12-24T21:30
+    #[arg(long)]
+    at: Option<DateTime>,
+}
+
 pub static OPTIONS: LazyLock<Opt> = LazyLock::new(Opt::parse);
 
 #[tokio::main(flavor = "current_thread")]
@@ -155,9 +273,15 @@ async fn main() {
     color_eyre::install().unwrap();
 
     match &OPTIONS.cmd {
-        Command::Random { print } => random_command(*print).await.unwrap(),
+        Command::Random { print, dry_run, seed, opts } => {
+            random_command(*print, *dry_run, *seed, opts).await.unwrap()
+        }
+        #[cfg(unix)]
+        Command::Daemon { print, interval, restore, opts } => {
+            daemon::run(*print, *interval, *restore, opts).await
+        }
         #[cfg(unix)]
-        Command::Daemon { print } => daemon::run(*print).await,
+        Command::Ctl { request } => daemon::control::run(request.clone()).await.unwrap(),
         Command::Sync { clean_monitors } => sync(*clean_monitors).await,
         #[cfg(any(not(unix), feature = "x11"))]
         Command::Preview {
@@ -191,6 +315,16 @@ async fn main() {
         Command::Interactive { file } => {
             interactive::run(file).await.unwrap();
         }
+        Command::Ban { file } => state::bans::ban(file).unwrap(),
+        Command::Unban { file } => state::bans::unban(file).unwrap(),
+        Command::Pin { monitor, file } => state::pins::pin(*monitor, file).await.unwrap(),
+        Command::Unpin { monitor } => state::pins::unpin(*monitor).await.unwrap(),
+        Command::Rate { rating, monitor } => state::ratings::rate(Some(*rating), *monitor).unwrap(),
+        Command::Unrate { monitor } => state::ratings::rate(None, *monitor).unwrap(),
+        Command::Current { json } => state::current::print(*json).unwrap(),
+        Command::History => state::history::print(),
+        Command::Previous { monitor } => previous_command(*monitor).await.unwrap(),
+        Command::Dupes { distance } => dupes::run(*distance).unwrap(),
         Command::ListMonitors => print_monitors().await,
         #[cfg(feature = "opencl")]
         Command::ShowGpus => print_gpus(),
@@ -198,30 +332,127 @@ async fn main() {
 }
 
 
-async fn random_command(print: bool) -> Result<()> {
+async fn random_command(
+    print: bool,
+    dry_run: bool,
+    seed: Option<u64>,
+    opts: &SelectionOpts,
+) -> Result<()> {
+    // Nothing is set, so there's no need for the daemon.
+    if dry_run {
+        let mut con = monitors::init();
+        let monitors = con.list_monitors().await?;
+        return random(&mut con, monitors, print, opts, Drawer::dry_run(seed)?, None).await;
+    }
+
+    #[cfg(all(unix, not(feature = "x11")))]
+    {
+        return random_daemon(print, opts).await;
+    }
+    #[cfg(any(not(unix), feature = "x11"))]
+    {
+        let mut con = monitors::init();
+        if con.requires_persistence() {
+            return random_daemon(print, opts).await;
+        }
+
+        let monitors = con.list_monitors().await?;
+        random(&mut con, monitors, print, opts, Drawer::new(), None).await
+    }
+}
+
+async fn previous_command(monitor: Option<usize>) -> Result<()> {
     #[cfg(all(unix, not(feature = "x11")))]
     {
-        pkill_wayland();
+        let _ = monitor;
+        println!("Previous is unsupported on Wayland.");
         return Ok(());
     }
     #[cfg(any(not(unix), feature = "x11"))]
     {
         let mut con = monitors::init();
         if con.requires_persistence() {
-            pkill_wayland();
+            println!("Previous is unsupported on Wayland.");
             return Ok(());
         }
 
         let monitors = con.list_monitors().await?;
-        random(&mut con, monitors, print).await
+        previous(&mut con, monitors, monitor).await
     }
 }
 
-fn pkill_wayland() {
+// Restores the previous wallpapers, keeping the current wallpapers on any other monitors.
+#[cfg(any(not(unix), feature = "x11"))]
+async fn previous(
+    con: &mut Connection,
+    monitors: Vec<Monitor>,
+    monitor: Option<usize>,
+) -> Result<()> {
+    let monitor = match monitor {
+        Some(n) => match monitors.get(n) {
+            Some(m) => Some((n, m)),
+            None => {
+                println!("No monitor {n}, see list-monitors");
+                return Ok(());
+            }
+        },
+        None => None,
+    };
+
+    let previous = History::load().previous(monitor);
+    if previous.is_empty() {
+        println!("No previous wallpapers in history");
+        return Ok(());
+    }
+
+    let current = Current::load();
+    let selection = monitors
+        .iter()
+        .enumerate()
+        .map(|(i, m)| {
+            previous
+                .iter()
+                .find(|s| s.matches(i, m))
+                .or_else(|| current.get(i, m))
+                .map(|s| s.original.clone())
+                // It may have been deleted or moved since
+                .filter(|wid| wid.original_abs_path().is_file())
+        })
+        .collect();
+
+    show(con, monitors, selection, false).await
+}
+
+// Asks the daemon to change wallpapers over its socket, falling back to signalling it.
+#[cfg_attr(not(unix), allow(unused))]
+async fn random_daemon(print: bool, opts: &SelectionOpts) -> Result<()> {
+    #[cfg(unix)]
+    if let Some(stream) = daemon::control::connect().await {
+        if opts.at.is_some() {
+            println!("--at is ignored, the daemon always selects for the current time.");
+        }
+
+        let request =
+            daemon::control::Request::Next { monitor: None, collection: opts.collection.clone() };
+        let output = daemon::control::request(stream, &request).await?;
+        if print {
+            print!("{output}");
+        }
+        return Ok(());
+    }
+
+    pkill_wayland(opts);
+    Ok(())
+}
+
+fn pkill_wayland(opts: &SelectionOpts) {
     println!(
         "Random is unsupported in this environment, attempting to signal daemon by name using \
          pkill.\nPrefer calling pkill or similar directly instead."
     );
+    if opts.collection.is_some() || opts.at.is_some() {
+        println!("--collection and --at are ignored, start the daemon with --collection instead.");
+    }
     if let Some(arg0) = std::env::args().next()
         && let Some(name) = Path::new(&arg0).file_name()
     {
@@ -236,15 +467,19 @@ fn pkill_wayland() {
     }
 }
 
-async fn random(con: &mut Connection, monitors: Vec<Monitor>, print: bool) -> Result<()> {
+async fn random(
+    con: &mut Connection,
+    monitors: Vec<Monitor>,
+    print: bool,
+    opts: &SelectionOpts,
+    mut drawer: Drawer,
+    unchanged: Option<Vec<Option<OriginalWallpaperID>>>,
+) -> Result<()> {
     if monitors.is_empty() {
         println!("No monitors detected");
         return Ok(());
     }
 
-    let tdir = LazyLock::new(make_tdir as _);
-
-
     // This will only be beneficial on cache misses, but can't hurt.
     if monitors::supports_memory_papers() {
         OPTIMISTIC_CACHE.get_or_init(|| {
@@ -252,59 +487,214 @@ async fn random(con: &mut Connection, monitors: Vec<Monitor>, print: bool) -> Re
         });
     }
 
-    // Opening the shuffler should only fail if it is already in use
-    let mut tries = 3;
-    let mut shuffler = loop {
-        let wallpapers = get_all_originals()?;
-        if wallpapers.is_empty() {
-            println!("No wallpapers found");
-            return Ok(());
+    let Some(selection) = select(&monitors, opts, &mut drawer, unchanged).await? else {
+        return Ok(());
+    };
+
+    if drawer.dry_run {
+        for (m, wid) in monitors.iter().zip(&selection) {
+            match wid {
+                Some(wid) => {
+                    println!("Would select {:?} for monitor {m:?}", wid.original_abs_path());
+                }
+                None => println!("Would select nothing for monitor {m:?}"),
+            }
         }
+    } else {
+        show(con, monitors, selection, print).await?;
+    }
 
-        let options = Options::default().keep_unrecognized(true);
+    drawer.finish()
+}
 
-        match Shuffler::new(&CONFIG.database, options, Some(wallpapers)) {
-            Ok(shuffler) => break shuffler,
-            Err(e) if tries == 0 => {
-                return Err(e.into());
+// Selects wallpapers for each monitor, in the same order as monitors, without processing them.
+// Monitors with an unchanged wallpaper keep it. Returns None if there were no wallpapers to select
+// from.
+async fn select(
+    monitors: &[Monitor],
+    opts: &SelectionOpts,
+    drawer: &mut Drawer,
+    unchanged: Option<Vec<Option<OriginalWallpaperID>>>,
+) -> Result<Option<Vec<Option<OriginalWallpaperID>>>> {
+    let pins = Pins::load();
+    let mut selection: Vec<_> = monitors
+        .iter()
+        .enumerate()
+        .map(|(i, m)| {
+            pins.get(i, m)
+                .cloned()
+                .or_else(|| unchanged.as_ref().and_then(|u| u[i].clone()))
+        })
+        .collect();
+    let kept: Vec<_> = selection.iter().flatten().cloned().collect();
+
+    // Pinned or unchanged wallpapers are never shown on other monitors at the same time.
+    let mut wallpapers = selection::filter(get_all_originals()?, opts)?;
+    wallpapers.retain(|w| !kept.contains(w));
+    // Directory order isn't stable, and seeded dry runs must draw the same way every time.
+    wallpapers.sort_unstable();
+
+    // Spanning wallpapers are only ever shown spanned.
+    let (spanning, wallpapers): (Vec<_>, Vec<_>) = wallpapers.into_iter().partition(span::included);
+
+    let resolutions = Resolutions::load(&wallpapers, !drawer.dry_run)?;
+    let pools = selection::pools::split(wallpapers, monitors);
+    if kept.is_empty() && spanning.is_empty() && pools.iter().all(|p| p.wallpapers.is_empty()) {
+        println!("No wallpapers found");
+        return Ok(None);
+    }
+
+    let ratings = Ratings::load();
+    let recent = selection::series::recent();
+
+    for (span, l) in span::layouts(monitors) {
+        if l.slices.iter().any(|s| selection[s.index].is_some()) {
+            continue;
+        }
+
+        let mut candidates: Vec<_> = spanning
+            .iter()
+            .filter(|w| selection::pools::glob_matches(&span.include, w))
+            .cloned()
+            .collect();
+        if candidates.is_empty() {
+            println!("No spanning wallpapers found for monitors {:?}", span.monitors);
+            continue;
+        }
+        selection::series::avoid(
+            &mut candidates,
+            &selection::series::taken(&recent, selection.iter().flatten()),
+        );
+
+        let wid = drawer.draw(candidates, 1).await?.swap_remove(0);
+        for s in &l.slices {
+            selection[s.index] = Some(wid.clone());
+        }
+    }
+
+    for mut pool in pools {
+        pool.monitors.retain(|i| selection[*i].is_none());
+        if pool.monitors.is_empty() {
+            continue;
+        }
+
+        // Each unit of monitors shares one wallpaper.
+        let units = selection::units(&pool.monitors, monitors);
+        let unit_monitors: Vec<Vec<_>> =
+            units.iter().map(|u| u.iter().map(|i| &monitors[*i]).collect()).collect();
+
+        pool.wallpapers
+            .retain(|w| unit_monitors.iter().any(|u| selection::fits(w, &resolutions, u)));
+        if pool.wallpapers.is_empty() {
+            let pool_monitors: Vec<_> = pool.monitors.iter().map(|i| &monitors[*i]).collect();
+            println!("No wallpapers found for monitors {pool_monitors:?}");
+            continue;
+        }
+        selection::series::avoid(
+            &mut pool.wallpapers,
+            &selection::series::taken(&recent, selection.iter().flatten()),
+        );
+
+        // Everything is drawn at once, so the shuffler only has to be opened once.
+        let n = units.len() + CONFIG.extra_candidates;
+        let eligible =
+            selection::weights::eligible(pool.wallpapers.clone(), n, &ratings, &mut drawer.rng);
+        let candidates = drawer.draw(eligible, n).await?;
+
+        let mut assigned = selection::assign(candidates, &unit_monitors, &resolutions);
+
+        // Replace any wallpapers that are too small for their monitors with ones that fit, and any
+        // from a series that is already on screen.
+        for (u, unit) in unit_monitors.iter().enumerate() {
+            let taken =
+                selection::series::taken(&recent, selection.iter().flatten().chain(&assigned[..u]));
+            let fits = selection::fits(&assigned[u], &resolutions, unit);
+            if fits && !selection::series::is_taken(&assigned[u], &taken) {
+                continue;
             }
-            Err(e) => {
-                println!("Error opening shuffler: {e}, retrying");
-                // pseudo-random enough that multiple processes with the similar pids should get
-                // different delays.
-                let delay = process::id().reverse_bits() as u64 % 20000 + 2000;
-                sleep(Duration::from_millis(delay)).await
+
+            let mut fitting: Vec<_> = pool
+                .wallpapers
+                .iter()
+                .filter(|w| !assigned.contains(w) && selection::fits(w, &resolutions, unit))
+                .cloned()
+                .collect();
+            if fits {
+                fitting.retain(|w| !selection::series::is_taken(w, &taken));
+            } else {
+                selection::series::avoid(&mut fitting, &taken);
             }
+
+            if fitting.is_empty() {
+                if !fits {
+                    println!(
+                        "No wallpapers are large enough for monitors {unit:?}, ignoring \
+                         max_upscale"
+                    );
+                }
+                continue;
+            }
+
+            assigned[u] = drawer.draw(fitting, 1).await?.swap_remove(0);
         }
-        tries -= 1;
-    };
 
-    // https://github.com/rust-lang/rust-clippy/issues/9219
-    let selection: Vec<_> = if !CONFIG.single_wallpaper {
-        shuffler
-            .try_unique_n(monitors.len())
-            .unwrap()
-            .unwrap()
-            .into_iter()
-            .cloned()
-            .collect()
-    } else {
-        let paper = shuffler.next().unwrap().unwrap();
-        let mut sel = Vec::with_capacity(monitors.len());
-        sel.resize_with(monitors.len(), || paper.clone());
-        sel
-    };
+        for (unit, wid) in units.into_iter().zip(assigned) {
+            for i in unit {
+                selection[i] = Some(wid.clone());
+            }
+        }
+    }
+
+    Ok(Some(selection))
+}
+
+// Processes and sets the selected wallpapers, in the same order as monitors, then records them as
+// the current wallpapers. Monitors without a selected wallpaper are skipped.
+async fn show(
+    con: &mut Connection,
+    monitors: Vec<Monitor>,
+    selection: Vec<Option<OriginalWallpaperID>>,
+    print: bool,
+) -> Result<()> {
+    let tdir = LazyLock::new(make_tdir as _);
+    let ratings = print.then(Ratings::load);
+
+    let spans = span::active(&selection, &monitors);
+    let span_for = |i: usize| spans.iter().find(|(_, l)| l.contains(i));
 
-    let close_handle = thread::spawn(move || shuffler.close());
+    let shown: Vec<_> = selection
+        .iter()
+        .zip(&monitors)
+        .enumerate()
+        .filter_map(|(i, (wid, m))| {
+            let mut shown = Shown::new(i, m, wid.as_ref()?);
+            if let Some((wid, l)) = span_for(i) {
+                shown.cached = l.slice_path(wid, i)?;
+            }
+            Some(shown)
+        })
+        .collect();
 
     // Merge any duplicate wallpapers.
     let mut wids = Vec::new();
     let mut grouped_monitors: Vec<Vec<_>> = Vec::new();
+    let mut spanned_monitors = Vec::new();
 
     // O(n^2) but the real number of monitors will always be tiny
-    'outer: for (wid, m) in selection.into_iter().zip(monitors) {
+    'outer: for (i, (wid, m)) in selection.into_iter().zip(monitors).enumerate() {
+        let Some(wid) = wid else {
+            continue;
+        };
+
         if print {
-            println!("Selected {:?} for monitor {m:?}", wid.original_abs_path());
+            let rating = ratings.as_ref().and_then(|r| r.get(wid.slash_path()));
+            let rating = rating.map_or_else(String::new, |r| format!(", rated {r}/5"));
+            println!("Selected {:?} for monitor {m:?}{rating}", wid.original_abs_path());
+        }
+
+        if span_for(i).is_some() {
+            spanned_monitors.push((i, m));
+            continue;
         }
 
         for (i, w) in wids.iter().enumerate() {
@@ -335,14 +725,151 @@ async fn random(con: &mut Connection, monitors: Vec<Monitor>, print: bool) -> Re
         });
     }
 
+    for (wid, l) in &spans {
+        span::process(wid, l, &tdir, false);
+    }
+
     if !closing::closed() {
-        con.set_wallpapers(combined.as_slice(), false).await?;
+        let mut paths_monitors: HashMap<_, Vec<_>> = HashMap::new();
+        for (wid, ms) in &combined {
+            for m in *ms {
+                paths_monitors
+                    .entry(wid.cached_abs_path(m, &wid.get_props(m)))
+                    .or_default()
+                    .push(m);
+            }
+        }
+        for (i, m) in &spanned_monitors {
+            if let Some((wid, l)) = span_for(*i)
+                && let Some(p) = l.slice_path(wid, *i)
+            {
+                paths_monitors.entry(p).or_default().push(m);
+            }
+        }
+
+        con.set_paths(paths_monitors).await?;
+
+        let current = Current { monitors: shown.clone() };
+        if let Err(e) = current.save().and_then(|()| History::record(shown)) {
+            println!("Failed to record current wallpapers: {e}");
+        }
     }
 
-    close_handle.join().unwrap()?;
     Ok(())
 }
 
+// Draws wallpapers from the shuffler. Only one shuffler can have the database open at once, so
+// each draw waits for the previous one to finish closing. Dry runs use a copy of the database so
+// nothing is recorded, and seeded dry runs draw uniformly so they're reproducible.
+struct Drawer {
+    rng: StdRng,
+    seeded: bool,
+    dry_run: bool,
+    // Copy of the database for unseeded dry runs
+    database: Option<TempDir>,
+    close_handle: Option<thread::JoinHandle<Result<()>>>,
+}
+
+impl Drawer {
+    fn new() -> Self {
+        Self {
+            rng: StdRng::from_os_rng(),
+            seeded: false,
+            dry_run: false,
+            database: None,
+            close_handle: None,
+        }
+    }
+
+    fn dry_run(seed: Option<u64>) -> Result<Self> {
+        if let Some(seed) = seed {
+            return Ok(Self {
+                rng: StdRng::seed_from_u64(seed),
+                seeded: true,
+                dry_run: true,
+                ..Self::new()
+            });
+        }
+
+        let tdir = make_tdir();
+        let database = tdir.path().join("database");
+        create_dir(&database)?;
+        if CONFIG.database.is_dir() {
+            for entry in read_dir(&CONFIG.database)? {
+                let entry = entry?;
+                if entry.file_type()?.is_file() {
+                    copy(entry.path(), database.join(entry.file_name()))?;
+                }
+            }
+        }
+
+        Ok(Self { dry_run: true, database: Some(tdir), ..Self::new() })
+    }
+
+    async fn draw(
+        &mut self,
+        wallpapers: Vec<OriginalWallpaperID>,
+        n: usize,
+    ) -> Result<Vec<OriginalWallpaperID>> {
+        if self.seeded {
+            // Like try_unique_n, only repeat wallpapers when there aren't enough.
+            let mut selected = Vec::with_capacity(n);
+            while selected.len() < n && !wallpapers.is_empty() {
+                let count = (n - selected.len()).min(wallpapers.len());
+                selected.extend(wallpapers.choose_multiple(&mut self.rng, count).cloned());
+            }
+            return Ok(selected);
+        }
+
+        if let Some(handle) = self.close_handle.take() {
+            handle.join().unwrap()?;
+        }
+
+        let database = self
+            .database
+            .as_ref()
+            .map_or_else(|| CONFIG.database.clone(), |d| d.path().join("database"));
+        let mut shuffler = open_shuffler(&database, wallpapers).await?;
+
+        let selected = shuffler.try_unique_n(n).unwrap().unwrap().into_iter().cloned().collect();
+
+        self.close_handle = Some(thread::spawn(move || Ok(shuffler.close()?)));
+        Ok(selected)
+    }
+
+    fn finish(self) -> Result<()> {
+        if let Some(handle) = self.close_handle {
+            handle.join().unwrap()?;
+        }
+        Ok(())
+    }
+}
+
+async fn open_shuffler(
+    database: &Path,
+    wallpapers: Vec<OriginalWallpaperID>,
+) -> Result<Shuffler<OriginalWallpaperID>> {
+    let mut tries = 3;
+    loop {
+        let options = Options::default().keep_unrecognized(true);
+
+        match Shuffler::new(database, options, Some(wallpapers.clone())) {
+            Ok(shuffler) => return Ok(shuffler),
+            Err(e) if tries == 0 => {
+                return Err(e.into());
+            }
+            Err(e) => {
+                println!("Error opening shuffler: {e}, retrying");
+                // pseudo-random enough that multiple processes with the similar pids should get
+                // different delays.
+                let delay = process::id().reverse_bits() as u64 % 20000 + 2000;
+                sleep(Duration::from_millis(delay)).await
+            }
+        }
+        tries -= 1;
+    }
+}
+
 async fn sync(clean_monitors: bool) {
     let tdir = LazyLock::new(make_tdir as _);
 
@@ -368,6 +895,18 @@ async fn sync(clean_monitors: bool) {
         return;
     }
 
+    let (spanning, normal): (Vec<_>, Vec<_>) = wallpapers.iter().partition(|w| span::included(w));
+    let layouts = span::layouts(&monitors);
+    let spanned: Vec<_> = layouts
+        .iter()
+        .flat_map(|(span, l)| {
+            spanning
+                .iter()
+                .filter(|w| selection::pools::glob_matches(&span.include, w))
+                .map(move |w| (*w, l))
+        })
+        .collect();
+
     // Rayon is too parallel for this, need something dumber that isn't as proactive to make the
     // order somewhat consistent.
     let index = AtomicUsize::new(0);
@@ -376,8 +915,8 @@ async fn sync(clean_monitors: bool) {
             s.spawn(|_s| {
                 while !closing::closed() {
                     let i = index.fetch_add(1, Ordering::Relaxed);
-                    match wallpapers.get(i) {
-                        Some(wid) => Wallpaper::new(wid, &monitors, &tdir).process(true),
+                    match normal.get(i) {
+                        Some(wid) => Wallpaper::new(*wid, &monitors, &tdir).process(true),
                         None => return,
                     }
                 }
@@ -390,12 +929,25 @@ async fn sync(clean_monitors: bool) {
         return;
     }
 
-    let valid_files: HashSet<_> = wallpapers
+    // Spanned wallpapers are already processed in parallel internally.
+    for (wid, l) in &spanned {
+        if closing::closed() {
+            return;
+        }
+        span::process(wid, l, &tdir, false);
+    }
+
+    let valid_files: HashSet<_> = normal
         .iter()
         .flat_map(|w| monitors.iter().map(|m| w.cached_abs_path(m, &w.get_props(m))))
+        .chain(spanned.iter().flat_map(|(w, l)| l.paths(w)))
         .collect();
 
-    let monitor_dirs: HashSet<_> = monitors.iter().map(Monitor::cache_dir).collect();
+    let monitor_dirs: HashSet<_> = monitors
+        .iter()
+        .map(Monitor::cache_dir)
+        .chain(layouts.iter().map(|(_, l)| l.canvas.cache_dir()))
+        .collect();
 
     let walk = WalkDir::new(&CONFIG.cache_directory)
         .into_iter()
@@ -430,7 +982,23 @@ async fn sync(clean_monitors: bool) {
         }
     });
 
+    let mut resolutions = Resolutions::load(&wallpapers, true).unwrap();
+    resolutions.prune(&wallpapers).unwrap();
+    for w in &normal {
+        for m in &monitors {
+            if let Some((scale, limit)) = selection::too_small(w, &resolutions, m) {
+                println!(
+                    "{:?} needs {scale:.1}x upscaling for monitor {m:?}, more than {limit}x",
+                    w.slash_path()
+                );
+            }
+        }
+    }
+
+    // Banned wallpapers keep their properties in case they're unbanned later.
+    let bans = Bans::load();
     let mut props_copy = PROPERTIES.read().unwrap().clone();
+    props_copy.retain(|k, _| !bans.contains(k));
     for w in wallpapers {
         props_copy.remove(w.slash_path());
     }
@@ -492,8 +1060,8 @@ async fn print_monitors() {
         return;
     }
 
-    for m in monitors {
-        println!("{m:?}");
+    for (i, m) in monitors.iter().enumerate() {
+        println!("{i}: {m:?}");
     }
 }
 
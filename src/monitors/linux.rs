@@ -1,13 +1,15 @@
 use std::collections::HashMap;
 use std::collections::hash_map::Entry;
+use std::path::PathBuf;
 
-use color_eyre::Result;
+use color_eyre::{Report, Result};
 #[cfg(feature = "x11")]
 use {
     std::future,
     std::sync::atomic::{AtomicBool, Ordering},
 };
 
+use crate::closing;
 use crate::directories::ids::WallpaperID;
 
 mod wayland;
@@ -30,6 +32,14 @@ enum Kind {
 
 pub struct Connection(Kind);
 
+// Changes seen while polling.
+pub enum Update {
+    // Monitors that were added or changed
+    Monitors(Vec<Monitor>),
+    // The connection failed, like when the compositor restarts, and needs to be reconnected
+    Disconnected(Report),
+}
+
 pub fn init() -> Connection {
     #[cfg(feature = "x11")]
     {
@@ -46,10 +56,12 @@ pub fn init() -> Connection {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Monitor {
     pub width: u32,
     pub height: u32,
+    // Connector name like DP-1, if the compositor or X server provides one.
+    pub connector: Option<String>,
     // For x11
     #[cfg(feature = "x11")]
     top: i32,
@@ -59,6 +71,32 @@ pub struct Monitor {
     name: u32,
 }
 
+impl Monitor {
+    // A canvas covering several monitors, only used for processing spanned wallpapers.
+    pub const fn canvas(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            connector: None,
+            #[cfg(feature = "x11")]
+            top: 0,
+            #[cfg(feature = "x11")]
+            left: 0,
+            name: u32::MAX,
+        }
+    }
+
+    // Position in the combined desktop as (left, top), if known. Wayland doesn't tell clients.
+    pub fn position(&self) -> Option<(i32, i32)> {
+        #[cfg(feature = "x11")]
+        if !IS_WAYLAND.load(Ordering::Relaxed) {
+            return Some((self.left, self.top));
+        }
+
+        None
+    }
+}
+
 impl Connection {
     pub async fn list_monitors(&mut self) -> Result<Vec<Monitor>> {
         match &mut self.0 {
@@ -84,6 +122,14 @@ impl Connection {
             }
         }
 
+        self.set_paths(paths_monitors).await
+    }
+
+    // Sets already processed files, every monitor should be included.
+    pub async fn set_paths(
+        &mut self,
+        paths_monitors: HashMap<PathBuf, Vec<&Monitor>>,
+    ) -> Result<()> {
         match &mut self.0 {
             Kind::Wayland(wcon) => wcon.set_wallpapers(paths_monitors).await,
             #[cfg(feature = "x11")]
@@ -94,16 +140,37 @@ impl Connection {
         }
     }
 
-    // Keeps any underlying connection alive and up-to-date. Will only return if the connection is
-    // unexpectedly closed.
-    pub async fn poll(&mut self) -> Result<Vec<Monitor>> {
+    // Keeps any underlying connection alive and up-to-date. Reconnecting is left to the caller,
+    // since it can take a while.
+    pub async fn poll(&mut self) -> Update {
         match &mut self.0 {
-            Kind::Wayland(wcon) => wcon.poll().await,
+            Kind::Wayland(wcon) => match wcon.poll().await {
+                Ok(monitors) => Update::Monitors(monitors),
+                Err(e) => Update::Disconnected(e),
+            },
             #[cfg(feature = "x11")]
             Kind::X => future::pending().await,
         }
     }
 
+    // Replaces a connection that failed with error e, returning the new monitors. Only fails if
+    // that isn't possible.
+    pub async fn reconnect(&mut self, e: Report) -> Result<Vec<Monitor>> {
+        match &mut self.0 {
+            Kind::Wayland(_) if closing::closed() => Err(e),
+            Kind::Wayland(wcon) => {
+                println!("Lost connection to the compositor: {e}, reconnecting");
+                let Some(new) = wayland::reconnect().await else {
+                    return Err(e.wrap_err("Unable to reconnect to the compositor"));
+                };
+                *wcon = new;
+                wcon.list_monitors().await
+            }
+            #[cfg(feature = "x11")]
+            Kind::X => Err(e),
+        }
+    }
+
     #[cfg(feature = "x11")]
     pub const fn requires_persistence(&self) -> bool {
         match &self.0 {
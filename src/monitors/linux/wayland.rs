@@ -20,7 +20,7 @@ use nix::errno::Errno;
 use tokio::io::unix::AsyncFd;
 use tokio::select;
 use tokio::sync::oneshot;
-use tokio::time::{Instant, sleep_until, timeout};
+use tokio::time::{Instant, sleep, sleep_until, timeout};
 use wayland_client::backend::WaylandError;
 use wayland_client::protocol::wl_buffer::WlBuffer;
 use wayland_client::protocol::wl_compositor::WlCompositor;
@@ -45,6 +45,7 @@ use wayland_protocols_wlr::layer_shell::v1::client::zwlr_layer_surface_v1::{
 };
 
 use crate::closing::closed;
+use crate::config::CONFIG;
 use crate::monitors::Monitor;
 use crate::processing::WORKER;
 use crate::wallpaper::OPTIMISTIC_CACHE;
@@ -65,9 +66,33 @@ pub fn init() -> Option<Conn> {
     })
 }
 
+// Retries init() after the compositor goes away, backing off up to a few seconds between attempts,
+// until wayland_reconnect has passed.
+pub async fn reconnect() -> Option<Conn> {
+    let period = Duration::try_from(CONFIG.wayland_reconnect).unwrap_or_default();
+    let deadline = Instant::now() + period;
+    let mut delay = Duration::from_millis(100);
+
+    loop {
+        if closed() {
+            return None;
+        }
+        if let Some(con) = init() {
+            return Some(con);
+        }
+        if Instant::now() + delay > deadline {
+            return None;
+        }
+
+        sleep(delay).await;
+        delay = (delay * 2).min(Duration::from_secs(5));
+    }
+}
+
 #[derive(Debug)]
 struct Output {
     wl_output: WlOutput,
+    connector: Option<String>,
     fract_scale: Option<WpFractionalScaleV1>,
     surface: Option<WlSurface>,
     viewport: Option<WpViewport>,
@@ -215,6 +240,7 @@ impl Conn {
                 Monitor {
                     width: w as u32,
                     height: h as u32,
+                    connector: out.connector.clone(),
                     #[cfg(feature = "x11")]
                     top: 0,
                     #[cfg(feature = "x11")]
@@ -552,11 +578,13 @@ impl Dispatch<WlRegistry, ()> for AppData {
         // `global` event, which signals a new available global.
         // When receiving this event, we just print its characteristics in this example.
         match event {
-            wl_registry::Event::Global { name, interface, .. } => {
+            wl_registry::Event::Global { name, interface, version } => {
                 if interface == WlOutput::interface().name {
-                    let wl_output = reg.bind::<WlOutput, _, _>(name, 2, qh, name);
+                    // Version 4 is necessary for connector names, but isn't required.
+                    let wl_output = reg.bind::<WlOutput, _, _>(name, version.clamp(2, 4), qh, name);
                     let output = Output {
                         wl_output,
+                        connector: None,
                         fract_scale: None,
                         surface: None,
                         viewport: None,
@@ -606,6 +634,11 @@ impl Dispatch<WlOutput, u32> for AppData {
         _con: &Connection,
         qh: &QueueHandle<Self>,
     ) {
+        if let wl_output::Event::Name { name: connector } = &event {
+            let output = state.outputs.get_mut(name).unwrap();
+            output.connector = Some(connector.clone());
+        }
+
         if let wl_output::Event::Scale { factor } = event {
             let output = state.outputs.get_mut(name).unwrap();
             if output.int_scale != factor {
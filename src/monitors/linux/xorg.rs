@@ -1,6 +1,6 @@
 use core::slice;
 use std::collections::HashMap;
-use std::ffi::CString;
+use std::ffi::{CStr, CString};
 use std::path::PathBuf;
 use std::{env, mem, ptr};
 
@@ -47,11 +47,24 @@ pub(super) fn list_monitors() -> Vec<Monitor> {
                 XFree(xinerama_info.cast());
             }
         } else {
+            let names = monitor_names(dpy, root);
             let monitors = slice::from_raw_parts(xinerama_info, num as usize)
                 .iter()
                 .map(|si| Monitor {
                     width: si.width as u32,
                     height: si.height as u32,
+                    connector: names
+                        .iter()
+                        .find(|(geom, _)| {
+                            *geom
+                                == (
+                                    si.x_org.into(),
+                                    si.y_org.into(),
+                                    si.width.into(),
+                                    si.height.into(),
+                                )
+                        })
+                        .map(|(_, n)| n.clone()),
                     top: si.y_org as i32,
                     left: si.x_org as i32,
                     name: 0,
@@ -75,9 +88,15 @@ pub(super) fn list_monitors() -> Vec<Monitor> {
                 let crtc = XRRGetCrtcInfo(dpy, resources, (*info).crtc);
                 let cinfo = &*crtc;
 
+                let name = slice::from_raw_parts(
+                    (*info).name.cast::<u8>(),
+                    (*info).nameLen.max(0) as usize,
+                );
+
                 monitors.push(Monitor {
                     width: cinfo.width,
                     height: cinfo.height,
+                    connector: Some(String::from_utf8_lossy(name).into_owned()),
                     top: cinfo.y,
                     left: cinfo.x,
                     name: 0,
@@ -95,6 +114,37 @@ pub(super) fn list_monitors() -> Vec<Monitor> {
     }
 }
 
+// Xinerama doesn't provide names, but RandR 1.5 monitors are cheap to query.
+// Returns ((x, y, width, height), name)
+unsafe fn monitor_names(dpy: *mut xlib::Display, root: u64) -> Vec<((i32, i32, i32, i32), String)> {
+    unsafe {
+        use xlib::*;
+        use xrandr::*;
+
+        let mut num = 0;
+        let monitors = XRRGetMonitors(dpy, root, 1, &raw mut num);
+        if monitors.is_null() {
+            return Vec::new();
+        }
+
+        let names = slice::from_raw_parts(monitors, num.max(0) as usize)
+            .iter()
+            .filter_map(|mi| {
+                let name = XGetAtomName(dpy, mi.name);
+                if name.is_null() {
+                    return None;
+                }
+
+                let s = CStr::from_ptr(name).to_string_lossy().into_owned();
+                XFree(name.cast());
+                Some(((mi.x, mi.y, mi.width, mi.height), s))
+            })
+            .collect();
+
+        XRRFreeMonitors(monitors);
+        names
+    }
+}
 
 #[derive(Debug)]
 struct MallocedImage(*mut i8, u32, u32);
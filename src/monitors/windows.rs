@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
 use std::time::Duration;
 use std::{io, thread};
 
@@ -22,7 +24,30 @@ pub fn init() -> Connection {
 pub struct Monitor {
     pub width: u32,
     pub height: u32,
+    // The device path, Windows has no friendlier stable identifier.
+    pub connector: Option<String>,
     pub path: U16CString,
+    left: i32,
+    top: i32,
+}
+
+impl Monitor {
+    // A canvas covering several monitors, only used for processing spanned wallpapers.
+    pub fn canvas(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            connector: None,
+            path: U16CString::new(),
+            left: 0,
+            top: 0,
+        }
+    }
+
+    // Position in the combined desktop as (left, top).
+    pub const fn position(&self) -> Option<(i32, i32)> {
+        Some((self.left, self.top))
+    }
 }
 
 // Just return an empty monitors list rather than panicking
@@ -52,7 +77,10 @@ unsafe fn get_monitor(dtop: &IDesktopWallpaper, n: u32) -> Result<Option<Monitor
         Ok(Some(Monitor {
             width: (rect.right - rect.left) as u32,
             height: (rect.bottom - rect.top) as u32,
+            connector: Some(path.to_string_lossy()),
             path,
+            left: rect.left,
+            top: rect.top,
         }))
     }
 }
@@ -94,12 +122,25 @@ impl Connection {
         wallpapers: &[(&impl WallpaperID, &[Monitor])],
         _temp: bool,
     ) -> color_eyre::Result<()> {
-        // TODO -- maybe set legacy registry keys. Likely useless but I want to be sure.
+        let mut paths_monitors: HashMap<_, Vec<_>> = HashMap::new();
+        for (wid, ms) in wallpapers {
+            for m in *ms {
+                paths_monitors
+                    .entry(wid.cached_abs_path(m, &wid.get_props(m)))
+                    .or_default()
+                    .push(m);
+            }
+        }
 
-        let wallmons: Vec<_> = wallpapers
-            .iter()
-            .flat_map(move |(wid, ms)| ms.iter().map(move |m| (wid, m)))
-            .collect();
+        self.set_paths(paths_monitors).await
+    }
+
+    // Sets already processed files, every monitor should be included.
+    pub async fn set_paths(
+        &mut self,
+        paths_monitors: HashMap<PathBuf, Vec<&Monitor>>,
+    ) -> color_eyre::Result<()> {
+        // TODO -- maybe set legacy registry keys. Likely useless but I want to be sure.
 
         let r: Result<_, io::Error> = (|| unsafe {
             CoInitialize(None).unwrap();
@@ -110,10 +151,12 @@ impl Connection {
 
             desktop.SetPosition(DWPOS_CENTER)?;
 
-            for (wid, m) in wallmons {
-                let u16_path = U16CString::from_os_str(wid.cached_abs_path(m, &wid.get_props(m)))
-                    .expect("Invalid wallpaper path containing null");
-                desktop.SetWallpaper(PCWSTR(m.path.as_ptr()), PCWSTR(u16_path.as_ptr()))?;
+            for (p, ms) in &paths_monitors {
+                let u16_path =
+                    U16CString::from_os_str(p).expect("Invalid wallpaper path containing null");
+                for m in ms {
+                    desktop.SetWallpaper(PCWSTR(m.path.as_ptr()), PCWSTR(u16_path.as_ptr()))?;
+                }
             }
 
             drop(desktop);
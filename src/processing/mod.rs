@@ -41,6 +41,26 @@ pub static UPSCALING: LazyLock<ThreadPool> = LazyLock::new(|| {
         .expect("Error creating upscaling threadpool")
 });
 
+// Work nothing is waiting on, like the daemon preparing its next wallpapers, runs on a single low
+// priority thread instead.
+pub static BACKGROUND: LazyLock<ThreadPool> = LazyLock::new(|| {
+    ThreadPoolBuilder::new()
+        .thread_name(|u| format!("background-{u}"))
+        .start_handler(|_| lower_priority())
+        .panic_handler(handle_panic)
+        .num_threads(1)
+        .build()
+        .expect("Error creating background threadpool")
+});
+
+// Only affects the calling thread, and anything it starts, like upscalers.
+pub fn lower_priority() {
+    #[cfg(target_os = "linux")]
+    unsafe {
+        libc::setpriority(libc::PRIO_PROCESS, 0, 19);
+    }
+}
+
 
 fn handle_panic(_e: Box<dyn Any + Send>) {
     println!("Unexpected panic in thread {}", thread::current().name().unwrap_or("unnamed"));
@@ -0,0 +1,267 @@
+use color_eyre::Result;
+use color_eyre::eyre::bail;
+use glob::Pattern;
+use jiff::Zoned;
+
+use crate::SelectionOpts;
+use crate::config::CONFIG;
+use crate::directories::ids::{OriginalWallpaperID, WallpaperID};
+use crate::monitors::Monitor;
+use crate::state::resolutions::Resolutions;
+use crate::wallpaper::{UNFIT_COST, span};
+
+pub mod pools;
+pub mod ratings;
+mod schedule;
+pub mod series;
+pub mod weights;
+
+// Restricts the wallpapers to the requested collection, or to whatever is currently scheduled.
+pub fn filter(
+    wallpapers: Vec<OriginalWallpaperID>,
+    opts: &SelectionOpts,
+) -> Result<Vec<OriginalWallpaperID>> {
+    let globs = if let Some(name) = &opts.collection {
+        collection_globs(name)?
+    } else {
+        let now = opts.at.unwrap_or_else(|| Zoned::now().datetime());
+        match schedule::active(now) {
+            Some(s) => match &s.collection {
+                Some(name) => collection_globs(name)?,
+                None => &s.include,
+            },
+            None => return Ok(wallpapers),
+        }
+    };
+
+    Ok(wallpapers.into_iter().filter(|w| pools::glob_matches(globs, w)).collect())
+}
+
+fn collection_globs(name: &str) -> Result<&'static [Pattern]> {
+    let Some(globs) = CONFIG.collections.get(name) else {
+        bail!("Unknown collection {name:?}");
+    };
+    Ok(globs)
+}
+
+// Worse than any other match, so wallpapers that are too small are only used when nothing else
+// is available.
+const TOO_SMALL_COST: f64 = 1000.0;
+
+fn max_upscale(m: &Monitor) -> Option<f64> {
+    m.connector
+        .as_ref()
+        .and_then(|c| CONFIG.monitor_max_upscale.get(c))
+        .copied()
+        .or(CONFIG.max_upscale)
+}
+
+// Returns (needed, allowed) if the wallpaper would need to be upscaled more than allowed for this
+// monitor. Wallpapers with unknown resolutions are never too small.
+pub fn too_small(
+    wid: &OriginalWallpaperID,
+    resolutions: &Resolutions,
+    m: &Monitor,
+) -> Option<(f64, f64)> {
+    let limit = max_upscale(m)?;
+    let scale = resolutions.get(wid)?.upscale(&wid.get_props(m), m);
+    (scale > limit).then_some((scale, limit))
+}
+
+pub fn fits(wid: &OriginalWallpaperID, resolutions: &Resolutions, unit: &[&Monitor]) -> bool {
+    unit.iter().all(|m| too_small(wid, resolutions, m).is_none())
+}
+
+// Splits monitors, given as indices, into units that should each show a single wallpaper.
+// Monitors in the same configured group share a unit, and single_wallpaper puts every monitor in
+// one unit.
+pub fn units(indices: &[usize], monitors: &[Monitor]) -> Vec<Vec<usize>> {
+    if CONFIG.single_wallpaper {
+        return vec![indices.to_vec()];
+    }
+
+    let mut units: Vec<(Option<usize>, Vec<usize>)> = Vec::new();
+    for i in indices {
+        let group = monitors[*i]
+            .connector
+            .as_ref()
+            .and_then(|c| CONFIG.monitor_groups.iter().position(|g| g.contains(c)));
+
+        match units.iter_mut().find(|(g, _)| group.is_some() && *g == group) {
+            Some((_, unit)) => unit.push(*i),
+            None => units.push((group, vec![*i])),
+        }
+    }
+
+    units.into_iter().map(|(_, unit)| unit).collect()
+}
+
+// Monitors that have to change along with this one, because they share a group or a span.
+pub fn linked(index: usize, monitors: &[Monitor]) -> Vec<usize> {
+    let all: Vec<_> = (0..monitors.len()).collect();
+    let mut linked = units(&all, monitors)
+        .into_iter()
+        .find(|u| u.contains(&index))
+        .unwrap_or_else(|| vec![index]);
+
+    for (_, l) in span::layouts(monitors) {
+        if l.contains(index) {
+            linked.extend(l.slices.iter().map(|s| s.index));
+        }
+    }
+
+    linked.sort_unstable();
+    linked.dedup();
+    linked
+}
+
+// Matches candidates to units of monitors so that each unit gets the image that needs the least
+// cropping or upscaling across all of its monitors. Returns one wallpaper per unit, in the same
+// order as units.
+//
+// Any candidates beyond the number of units are discarded. Candidates are only reused for
+// multiple units when there aren't enough unique ones.
+pub fn assign(
+    selected: Vec<OriginalWallpaperID>,
+    units: &[Vec<&Monitor>],
+    resolutions: &Resolutions,
+) -> Vec<OriginalWallpaperID> {
+    assign_by(selected, units, |c, m| match resolutions.get(c) {
+        _ if too_small(c, resolutions, m).is_some() => TOO_SMALL_COST,
+        Some(r) => r.fit_cost(&c.get_props(m), m),
+        None => UNFIT_COST,
+    })
+}
+
+// Assigns candidates using the cost of showing each one on a single monitor.
+fn assign_by(
+    selected: Vec<OriginalWallpaperID>,
+    units: &[Vec<&Monitor>],
+    cost: impl Fn(&OriginalWallpaperID, &Monitor) -> f64,
+) -> Vec<OriginalWallpaperID> {
+    // try_unique_n will repeat wallpapers if there aren't enough.
+    let mut candidates = Vec::with_capacity(selected.len());
+    for wid in selected {
+        if !candidates.contains(&wid) {
+            candidates.push(wid);
+        }
+    }
+
+    if candidates.len() == 1 && !units.is_empty() {
+        return vec![candidates[0].clone(); units.len()];
+    }
+
+    let costs: Vec<Vec<f64>> = candidates
+        .iter()
+        .map(|c| units.iter().map(|u| u.iter().map(|m| cost(c, m)).sum()).collect())
+        .collect();
+
+    let mut used = vec![false; candidates.len()];
+    let mut current = Vec::with_capacity(units.len());
+    let mut best = (f64::INFINITY, Vec::new());
+    search(&costs, &mut used, &mut current, 0.0, &mut best);
+
+    best.1.into_iter().map(|i| candidates[i].clone()).collect()
+}
+
+// Exhaustive search with pruning. The number of monitors and candidates is always small.
+fn search(
+    costs: &[Vec<f64>],
+    used: &mut [bool],
+    current: &mut Vec<usize>,
+    total: f64,
+    best: &mut (f64, Vec<usize>),
+) {
+    if total >= best.0 {
+        return;
+    }
+
+    let m = current.len();
+    if m == costs.first().map_or(0, Vec::len) {
+        *best = (total, current.clone());
+        return;
+    }
+
+    let all_used = used.iter().all(|u| *u);
+
+    for c in 0..costs.len() {
+        if used[c] && !all_used {
+            continue;
+        }
+
+        let was_used = used[c];
+        used[c] = true;
+        current.push(c);
+        search(costs, used, current, total + costs[c][m], best);
+        current.pop();
+        used[c] = was_used;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn best(costs: &[Vec<f64>]) -> Vec<usize> {
+        let mut used = vec![false; costs.len()];
+        let mut best = (f64::INFINITY, Vec::new());
+        search(costs, &mut used, &mut Vec::new(), 0.0, &mut best);
+        best.1
+    }
+
+    #[test]
+    fn test_search_total_cost() {
+        // Giving the first unit its best fit would leave the second with a terrible one.
+        let costs = vec![vec![1.0, 2.0], vec![2.0, 100.0]];
+        assert_eq!(best(&costs), vec![1, 0]);
+
+        // Extra candidates are discarded.
+        let costs = vec![vec![5.0, 5.0], vec![5.0, 1.0], vec![1.0, 5.0]];
+        assert_eq!(best(&costs), vec![2, 1]);
+    }
+
+    #[test]
+    fn test_search_reuse() {
+        // Every candidate is used once before any is repeated.
+        let costs = vec![vec![1.0, 1.0, 1.0], vec![9.0, 9.0, 9.0]];
+        let assigned = best(&costs);
+        assert_eq!(assigned.len(), 3);
+        assert!(assigned.contains(&0) && assigned.contains(&1));
+
+        assert!(best(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_assign_orientation() {
+        let resolutions = Resolutions::parse(
+            r#"
+            [originals."portrait.png"]
+            mtime = 0
+            width = 1440
+            height = 2560
+
+            [originals."wide.png"]
+            mtime = 0
+            width = 3440
+            height = 1440
+            "#,
+        );
+        let portrait = OriginalWallpaperID::from_rel_path("portrait.png");
+        let wide = OriginalWallpaperID::from_rel_path("wide.png");
+
+        let ultrawide = Monitor::canvas(3440, 1440);
+        let vertical = Monitor::canvas(1440, 2560);
+        assert!(
+            resolutions.get(&portrait).unwrap().fit_cost(&None, &vertical)
+                < resolutions.get(&portrait).unwrap().fit_cost(&None, &ultrawide)
+        );
+
+        // Drawn in the wrong order for the monitors.
+        let units = [vec![&ultrawide], vec![&vertical]];
+        let cost = |c: &OriginalWallpaperID, m: &Monitor| {
+            resolutions.get(c).map_or(UNFIT_COST, |r| r.fit_cost(&None, m))
+        };
+        let assigned = assign_by(vec![portrait.clone(), wide.clone()], &units, cost);
+        assert_eq!(assigned, vec![wide, portrait]);
+    }
+}
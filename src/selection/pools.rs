@@ -0,0 +1,130 @@
+use glob::{MatchOptions, Pattern};
+
+use crate::config::{CONFIG, Pool, reduce_ratio};
+use crate::directories::ids::OriginalWallpaperID;
+use crate::monitors::Monitor;
+
+// "*" shouldn't cross directory boundaries, "**" does.
+static MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+#[derive(Debug)]
+pub struct MonitorPool {
+    pub wallpapers: Vec<OriginalWallpaperID>,
+    // Indices into the list of monitors
+    pub monitors: Vec<usize>,
+}
+
+pub fn glob_matches(patterns: &[Pattern], wid: &OriginalWallpaperID) -> bool {
+    patterns.iter().any(|p| p.matches_path_with(wid.slash_path(), MATCH_OPTIONS))
+}
+
+fn pool_matches(pool: &Pool, m: &Monitor) -> bool {
+    pool.connector.as_ref().is_none_or(|c| m.connector.as_ref() == Some(c))
+        && pool.resolution.is_none_or(|r| r == (m.width, m.height))
+        && pool.aspect_ratio.is_none_or(|r| r == reduce_ratio(m.width, m.height))
+}
+
+// Splits the wallpapers into one pool for each group of monitors that share a configured pool.
+// Monitors that don't match any configured pool draw from every wallpaper not claimed by any
+// configured pool. Wallpapers matching several pools only belong to the first, so they can't be
+// selected for two monitors at once.
+pub fn split(wallpapers: Vec<OriginalWallpaperID>, monitors: &[Monitor]) -> Vec<MonitorPool> {
+    split_between(&CONFIG.pools, wallpapers, monitors)
+}
+
+fn split_between(
+    pools: &[Pool],
+    wallpapers: Vec<OriginalWallpaperID>,
+    monitors: &[Monitor],
+) -> Vec<MonitorPool> {
+    if pools.is_empty() {
+        return vec![MonitorPool {
+            wallpapers,
+            monitors: (0..monitors.len()).collect(),
+        }];
+    }
+
+    let mut groups: Vec<(Option<usize>, Vec<usize>)> = Vec::new();
+    for (i, m) in monitors.iter().enumerate() {
+        let pool = pools.iter().position(|p| pool_matches(p, m));
+
+        match groups.iter_mut().find(|(p, _)| *p == pool) {
+            Some((_, ms)) => ms.push(i),
+            None => groups.push((pool, vec![i])),
+        }
+    }
+
+    let mut claimed: Vec<(Option<usize>, OriginalWallpaperID)> = wallpapers
+        .into_iter()
+        .map(|w| (pools.iter().position(|p| glob_matches(&p.include, &w)), w))
+        .collect();
+
+    groups
+        .into_iter()
+        .map(|(pool, monitors)| {
+            let (wallpapers, rest) = claimed.drain(..).partition(|(p, _)| *p == pool);
+            claimed = rest;
+
+            MonitorPool {
+                wallpapers: wallpapers.into_iter().map(|(_, w)| w).collect(),
+                monitors,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool(connector: &str, include: &[&str]) -> Pool {
+        Pool {
+            connector: Some(connector.to_string()),
+            resolution: None,
+            aspect_ratio: None,
+            include: include.iter().map(|g| Pattern::new(g).unwrap()).collect(),
+        }
+    }
+
+    fn monitor(connector: &str) -> Monitor {
+        let mut m = Monitor::canvas(1920, 1080);
+        m.connector = Some(connector.to_string());
+        m
+    }
+
+    fn wids(paths: &[&str]) -> Vec<OriginalWallpaperID> {
+        paths.iter().map(OriginalWallpaperID::from_rel_path).collect()
+    }
+
+    #[test]
+    fn test_split_overlapping() {
+        let pools = [pool("DP-1", &["portrait/**"]), pool("DP-2", &["portrait/tall/**", "wide/*"])];
+        let monitors = [monitor("DP-1"), monitor("DP-2"), monitor("HDMI-1")];
+        let wallpapers = wids(&["portrait/a.png", "portrait/tall/b.png", "wide/c.png", "d.png"]);
+
+        let split = split_between(&pools, wallpapers, &monitors);
+        assert_eq!(split.len(), 3);
+        assert_eq!(split[0].monitors, [0]);
+        assert_eq!(split[0].wallpapers, wids(&["portrait/a.png", "portrait/tall/b.png"]));
+        assert_eq!(split[1].wallpapers, wids(&["wide/c.png"]));
+        assert_eq!(split[2].monitors, [2]);
+        assert_eq!(split[2].wallpapers, wids(&["d.png"]));
+    }
+
+    #[test]
+    fn test_split_shared_pool() {
+        let pools = [pool("DP-1", &["*"])];
+        let monitors = [monitor("HDMI-1"), monitor("DP-1"), monitor("HDMI-2")];
+
+        let split = split_between(&pools, wids(&["a.png", "b/c.png"]), &monitors);
+        assert_eq!(split.len(), 2);
+        assert_eq!(split[0].monitors, [0, 2]);
+        assert_eq!(split[0].wallpapers, wids(&["b/c.png"]));
+        assert_eq!(split[1].monitors, [1]);
+        assert_eq!(split[1].wallpapers, wids(&["a.png"]));
+    }
+}
@@ -0,0 +1,9 @@
+use crate::config::CONFIG;
+use crate::directories::ids::OriginalWallpaperID;
+use crate::state::ratings::Ratings;
+
+// How much a wallpaper's rating multiplies its chances. Unrated wallpapers are treated as rated 3.
+pub fn weight(wid: &OriginalWallpaperID, ratings: &Ratings) -> f64 {
+    let rating = ratings.get(wid.slash_path()).unwrap_or(3);
+    CONFIG.rating_weights[usize::from(rating) - 1]
+}
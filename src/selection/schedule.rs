@@ -0,0 +1,72 @@
+use jiff::civil::DateTime;
+
+use crate::config::{CONFIG, Schedule};
+
+impl Schedule {
+    // Windows and date ranges can wrap around midnight or the end of the year.
+    fn active(&self, now: DateTime) -> bool {
+        let time_matches = match (self.start_time, self.end_time) {
+            (Some(start), Some(end)) if start <= end => start <= now.time() && now.time() < end,
+            (Some(start), Some(end)) => start <= now.time() || now.time() < end,
+            _ => true,
+        };
+
+        let today = (now.month(), now.day());
+        let date_matches = match (self.start_date, self.end_date) {
+            (Some(start), Some(end)) if start <= end => start <= today && today <= end,
+            (Some(start), Some(end)) => start <= today || today <= end,
+            _ => true,
+        };
+
+        time_matches && date_matches
+    }
+}
+
+// The first schedule that is active at the given time, if any.
+pub fn active(now: DateTime) -> Option<&'static Schedule> {
+    CONFIG.schedule.iter().find(|s| s.active(now))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use jiff::civil::{date, time};
+
+    use super::*;
+
+    fn schedule(times: Option<(i8, i8)>, dates: Option<((i8, i8), (i8, i8))>) -> Schedule {
+        Schedule {
+            start_time: times.map(|(s, _)| time(s, 0, 0, 0)),
+            end_time: times.map(|(_, e)| time(e, 0, 0, 0)),
+            start_date: dates.map(|(s, _)| s),
+            end_date: dates.map(|(_, e)| e),
+            collection: None,
+            include: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_time_windows() {
+        let evening = schedule(Some((20, 23)), None);
+        assert!(evening.active(date(2024, 3, 1).at(20, 0, 0, 0)));
+        assert!(!evening.active(date(2024, 3, 1).at(23, 0, 0, 0)));
+        assert!(!evening.active(date(2024, 3, 1).at(8, 0, 0, 0)));
+
+        let night = schedule(Some((20, 6)), None);
+        assert!(night.active(date(2024, 3, 1).at(22, 0, 0, 0)));
+        assert!(night.active(date(2024, 3, 1).at(3, 0, 0, 0)));
+        assert!(!night.active(date(2024, 3, 1).at(12, 0, 0, 0)));
+    }
+
+    #[test]
+    fn test_date_ranges() {
+        let december = schedule(None, Some(((12, 1), (12, 31))));
+        assert!(december.active(date(2024, 12, 31).at(12, 0, 0, 0)));
+        assert!(!december.active(date(2024, 11, 30).at(12, 0, 0, 0)));
+
+        let winter = schedule(Some((20, 6)), Some(((12, 15), (1, 15))));
+        assert!(winter.active(date(2025, 1, 2).at(21, 0, 0, 0)));
+        assert!(!winter.active(date(2025, 1, 2).at(12, 0, 0, 0)));
+        assert!(!winter.active(date(2025, 2, 2).at(21, 0, 0, 0)));
+    }
+}
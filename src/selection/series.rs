@@ -0,0 +1,75 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+use crate::config::CONFIG;
+use crate::directories::ids::OriginalWallpaperID;
+use crate::directories::series;
+use crate::state::history::History;
+
+// Series shown within the cooldown.
+pub fn recent() -> HashSet<PathBuf> {
+    if CONFIG.series_cooldown == 0 {
+        return HashSet::new();
+    }
+
+    History::load().recent(CONFIG.series_cooldown).filter_map(series).collect()
+}
+
+// Series that are on cooldown or already selected for another monitor.
+pub fn taken<'a>(
+    recent: &HashSet<PathBuf>,
+    selected: impl IntoIterator<Item = &'a OriginalWallpaperID>,
+) -> HashSet<PathBuf> {
+    let mut taken = recent.clone();
+    taken.extend(selected.into_iter().filter_map(series));
+    taken
+}
+
+pub fn is_taken(wid: &OriginalWallpaperID, taken: &HashSet<PathBuf>) -> bool {
+    series(wid).is_some_and(|s| taken.contains(&s))
+}
+
+// Removes wallpapers from taken series, unless that would remove all of them.
+pub fn avoid(wallpapers: &mut Vec<OriginalWallpaperID>, taken: &HashSet<PathBuf>) {
+    if wallpapers.iter().any(|w| !is_taken(w, taken)) {
+        wallpapers.retain(|w| !is_taken(w, taken));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wids(paths: &[&str]) -> Vec<OriginalWallpaperID> {
+        paths.iter().map(OriginalWallpaperID::from_rel_path).collect()
+    }
+
+    #[test]
+    fn test_series() {
+        let ws = wids(&["a/artist_01.png", "a/artist_2.jpg", "a/other_01.png", "b/artist_01.png"]);
+        let s: Vec<_> = ws.iter().map(series).collect();
+
+        assert_eq!(s[0], Some(PathBuf::from("a/artist_")));
+        assert_eq!(s[0], s[1]);
+        assert_ne!(s[0], s[2]);
+        assert_ne!(s[0], s[3]);
+
+        assert_eq!(series(&wids(&["a/cover.png"])[0]), None);
+    }
+
+    #[test]
+    fn test_avoid() {
+        let recent = HashSet::from([PathBuf::from("a/recent_")]);
+        let selected = wids(&["a/shown_01.png"]);
+        let taken = taken(&recent, &selected);
+
+        let mut ws = wids(&["a/recent_01.png", "a/shown_02.png", "a/fresh_01.png", "a/cover.png"]);
+        avoid(&mut ws, &taken);
+        assert_eq!(ws, wids(&["a/fresh_01.png", "a/cover.png"]));
+
+        // Better to repeat a series than to show nothing.
+        let mut ws = wids(&["a/recent_01.png", "a/shown_02.png"]);
+        avoid(&mut ws, &taken);
+        assert_eq!(ws.len(), 2);
+    }
+}
@@ -0,0 +1,134 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use rand::Rng;
+
+use crate::config::CONFIG;
+use crate::directories::ids::OriginalWallpaperID;
+use crate::state::ratings::Ratings;
+
+// Picks which wallpapers can be drawn this time, so a single shuffler can draw n of them while
+// still respecting directory and rating weights.
+//
+// Normally every wallpaper is equally likely, and directory weights multiply the chances of each
+// wallpaper inside them. In fair mode each directory is equally likely, regardless of how many
+// wallpapers are inside it, and weights multiply the chances of the whole directory. Ratings
+// multiply the chances of each wallpaper, and unrated wallpapers are treated as rated 3.
+pub fn eligible(
+    wallpapers: Vec<OriginalWallpaperID>,
+    n: usize,
+    ratings: &Ratings,
+    rng: &mut impl Rng,
+) -> Vec<OriginalWallpaperID> {
+    if CONFIG.directory_weights.is_empty() && !CONFIG.fair_directories && ratings.is_empty() {
+        return wallpapers;
+    }
+
+    let groups: Vec<_> = wallpapers.iter().map(group_for).collect();
+    let mut sizes: HashMap<&Option<PathBuf>, usize> = HashMap::new();
+    for g in &groups {
+        *sizes.entry(g).or_default() += 1;
+    }
+
+    let weights: Vec<_> = wallpapers
+        .iter()
+        .zip(&groups)
+        .map(|(w, g)| {
+            let mut weight =
+                g.as_ref().and_then(|d| CONFIG.directory_weights.get(d)).copied().unwrap_or(1.0);
+            if CONFIG.fair_directories {
+                weight /= sizes[g] as f64;
+            }
+            weight * super::ratings::weight(w, ratings)
+        })
+        .collect();
+
+    let Some(kept) = thin(&weights, n, rng) else {
+        println!("Unable to apply directory or rating weights, ignoring them");
+        return wallpapers;
+    };
+
+    let mut kept = kept.into_iter().peekable();
+    wallpapers
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| kept.next_if_eq(i).is_some())
+        .map(|(_, w)| w)
+        .collect()
+}
+
+// Keeps each index with a chance proportional to its weight, so the most heavily weighted are
+// always kept. If fewer than n are kept, more are added in a weighted random order. Returns
+// sorted indices, or None if no weights are positive.
+fn thin(weights: &[f64], n: usize, rng: &mut impl Rng) -> Option<Vec<usize>> {
+    let max = weights.iter().copied().filter(|w| w.is_finite()).fold(0.0, f64::max);
+    if max <= 0.0 {
+        return None;
+    }
+
+    let mut kept = Vec::new();
+    let mut rest = Vec::new();
+    for (i, w) in weights.iter().enumerate() {
+        if rng.random::<f64>() * max < *w {
+            kept.push(i);
+        } else if *w > 0.0 {
+            // Weighted sampling without replacement takes the largest keys.
+            rest.push((rng.random::<f64>().powf(1.0 / w), i));
+        }
+    }
+
+    if kept.len() < n {
+        rest.sort_unstable_by(|a, b| b.0.total_cmp(&a.0));
+        kept.extend(rest.into_iter().take(n - kept.len()).map(|(_, i)| i));
+        kept.sort_unstable();
+    }
+    Some(kept)
+}
+
+// The deepest configured directory containing this wallpaper. In fair mode, unconfigured
+// wallpapers are grouped by their parent directories.
+fn group_for(wid: &OriginalWallpaperID) -> Option<PathBuf> {
+    let configured = CONFIG
+        .directory_weights
+        .keys()
+        .filter(|d| wid.slash_path().starts_with(d))
+        .max_by_key(|d| d.components().count());
+
+    match configured {
+        Some(d) => Some(d.clone()),
+        None if CONFIG.fair_directories => wid.slash_path().parent().map(Path::to_path_buf),
+        None => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    use super::*;
+
+    #[test]
+    fn test_thin() {
+        let mut rng = StdRng::seed_from_u64(1);
+
+        // The heaviest are always kept and weightless ones only when nothing else is left.
+        for _ in 0..100 {
+            assert_eq!(thin(&[2.0, 0.0, 2.0], 1, &mut rng), Some(vec![0, 2]));
+        }
+        assert_eq!(thin(&[0.1, 0.0, 4.0, 0.1], 3, &mut rng), Some(vec![0, 2, 3]));
+        assert_eq!(thin(&[0.0, 0.0], 1, &mut rng), None);
+    }
+
+    #[test]
+    fn test_thin_proportional() {
+        let mut rng = StdRng::seed_from_u64(2);
+
+        let trials = 10000;
+        let kept = (0..trials)
+            .filter(|_| thin(&[4.0, 1.0], 1, &mut rng).unwrap().contains(&1))
+            .count();
+        let ratio = kept as f64 / trials as f64;
+        assert!((0.22..0.28).contains(&ratio), "{ratio}");
+    }
+}
@@ -0,0 +1,56 @@
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use color_eyre::Result;
+use color_eyre::eyre::bail;
+use serde::{Deserialize, Serialize};
+
+use crate::directories::resolve_original;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Bans {
+    // Forward slash separated paths relative to the originals directory.
+    banned: BTreeSet<PathBuf>,
+}
+
+impl Bans {
+    pub fn load() -> Self {
+        super::load("bans")
+    }
+
+    pub fn contains(&self, slash_path: &Path) -> bool {
+        self.banned.contains(slash_path)
+    }
+}
+
+pub fn ban(file: &Path) -> Result<()> {
+    let Some(wid) = resolve_original(file) else {
+        bail!("{file:?} is not a wallpaper in the originals directory");
+    };
+
+    let mut bans: Bans = super::try_load("bans")?;
+    if !bans.banned.insert(wid.slash_path().to_path_buf()) {
+        println!("{:?} was already banned", wid.slash_path());
+        return Ok(());
+    }
+
+    super::save("bans", &bans)?;
+    println!("Banned {:?}", wid.slash_path());
+    Ok(())
+}
+
+pub fn unban(file: &Path) -> Result<()> {
+    let mut bans: Bans = super::try_load("bans")?;
+
+    // Banned files may have been deleted or moved since, so also accept the stored path.
+    let removed = resolve_original(file).is_some_and(|wid| bans.banned.remove(wid.slash_path()))
+        || bans.banned.remove(file);
+
+    if !removed {
+        bail!("{file:?} was not banned");
+    }
+
+    super::save("bans", &bans)?;
+    println!("Unbanned {file:?}");
+    Ok(())
+}
@@ -0,0 +1,145 @@
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+use color_eyre::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::config::ImageProperties;
+use crate::directories::ids::{OriginalWallpaperID, WallpaperID};
+use crate::monitors::Monitor;
+
+// The wallpapers most recently set on each monitor, so other processes can act on them.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Current {
+    #[serde(default)]
+    pub monitors: Vec<Shown>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Shown {
+    // Position in the list of monitors, as shown by list-monitors.
+    pub index: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connector: Option<String>,
+    pub width: u32,
+    pub height: u32,
+    pub original: OriginalWallpaperID,
+    pub cached: PathBuf,
+    // The properties that were in effect for this monitor when it was set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub props: Option<ImageProperties>,
+}
+
+impl Shown {
+    pub fn new(index: usize, m: &Monitor, wid: &OriginalWallpaperID) -> Self {
+        let props = wid.get_props(m);
+        Self {
+            index,
+            connector: m.connector.clone(),
+            width: m.width,
+            height: m.height,
+            original: wid.clone(),
+            cached: wid.cached_abs_path(m, &props),
+            props,
+        }
+    }
+
+    pub fn matches(&self, index: usize, m: &Monitor) -> bool {
+        super::same_monitor(self.index, &self.connector, index, m)
+    }
+}
+
+impl Current {
+    pub fn load() -> Self {
+        super::load("current")
+    }
+
+    pub fn save(&self) -> Result<()> {
+        super::save("current", self)
+    }
+
+    pub fn get(&self, index: usize, m: &Monitor) -> Option<&Shown> {
+        self.monitors.iter().find(|s| s.matches(index, m))
+    }
+
+    // The wallpapers shown on each monitor, skipping any that no longer exist.
+    pub fn selection(&self, monitors: &[Monitor]) -> Vec<Option<OriginalWallpaperID>> {
+        monitors
+            .iter()
+            .enumerate()
+            .map(|(i, m)| {
+                self.get(i, m)
+                    .map(|s| s.original.clone())
+                    .filter(|wid| wid.original_abs_path().is_file())
+            })
+            .collect()
+    }
+
+    // The wallpapers shown on each monitor whose processed files are still up to date, so they can
+    // be set again without any work.
+    pub fn restorable(&self, monitors: &[Monitor]) -> Vec<Option<OriginalWallpaperID>> {
+        monitors
+            .iter()
+            .enumerate()
+            .map(|(i, m)| {
+                let s = self.get(i, m)?;
+                let cached = super::mtime(&s.cached)?;
+                let original = super::mtime(&s.original.original_abs_path())?;
+
+                (s.width == m.width && s.height == m.height && cached >= original)
+                    .then(|| s.original.clone())
+            })
+            .collect()
+    }
+}
+
+// What scripts see, with absolute paths.
+#[derive(Serialize)]
+struct Output<'a> {
+    index: usize,
+    connector: Option<&'a str>,
+    width: u32,
+    height: u32,
+    original: PathBuf,
+    cached: &'a Path,
+    props: Option<&'a ImageProperties>,
+}
+
+pub fn print(json: bool) -> Result<()> {
+    print!("{}", describe(json)?);
+    Ok(())
+}
+
+pub fn describe(json: bool) -> Result<String> {
+    let current = Current::load();
+
+    if json {
+        let out: Vec<_> = current
+            .monitors
+            .iter()
+            .map(|s| Output {
+                index: s.index,
+                connector: s.connector.as_deref(),
+                width: s.width,
+                height: s.height,
+                original: s.original.original_abs_path(),
+                cached: &s.cached,
+                props: s.props.as_ref(),
+            })
+            .collect();
+        return Ok(serde_json::to_string_pretty(&out)? + "\n");
+    }
+
+    if current.monitors.is_empty() {
+        return Ok("No wallpapers have been set\n".to_string());
+    }
+
+    let mut out = String::new();
+    for s in &current.monitors {
+        let connector = s.connector.as_deref().unwrap_or("unknown");
+        writeln!(out, "{} {connector} {}x{}", s.index, s.width, s.height)?;
+        writeln!(out, "  original: {}", s.original.original_abs_path().display())?;
+        writeln!(out, "  cached: {}", s.cached.display())?;
+    }
+    Ok(out)
+}
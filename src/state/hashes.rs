@@ -0,0 +1,40 @@
+use color_eyre::Result;
+use serde::{Deserialize, Serialize};
+
+use super::index::Index;
+use crate::directories::ids::OriginalWallpaperID;
+use crate::dupes::dhash;
+
+// Perceptual hashes of originals, since computing them means decoding every image.
+pub struct Hashes(Index<Hash>);
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Hash {
+    // Hex, TOML integers can't hold every u64.
+    hash: String,
+}
+
+impl Hashes {
+    // Loads the index, hashing any new or modified wallpapers.
+    pub fn load(wallpapers: &[OriginalWallpaperID]) -> Result<Self> {
+        let mut index = Index::load("hashes");
+
+        let stale = index.stale(wallpapers);
+        if !stale.is_empty() {
+            println!("Hashing {} wallpapers", stale.len());
+        }
+        if index.update(stale, |p| Some(Hash { hash: format!("{:016x}", dhash(p)?) })) {
+            index.save()?;
+        }
+
+        Ok(Self(index))
+    }
+
+    pub fn get(&self, wid: &OriginalWallpaperID) -> Option<u64> {
+        u64::from_str_radix(&self.0.get(wid)?.hash, 16).ok()
+    }
+
+    pub fn prune(&mut self, wallpapers: &[OriginalWallpaperID]) -> Result<()> {
+        self.0.prune(wallpapers)
+    }
+}
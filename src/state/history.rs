@@ -0,0 +1,146 @@
+use color_eyre::Result;
+use jiff::Timestamp;
+use jiff::tz::TimeZone;
+use serde::{Deserialize, Serialize};
+
+use super::current::Shown;
+use crate::config::CONFIG;
+use crate::directories::ids::OriginalWallpaperID;
+use crate::monitors::Monitor;
+
+// A bounded log of every set of wallpapers shown, oldest first.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct History {
+    #[serde(default)]
+    sets: Vec<Set>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Set {
+    time: Timestamp,
+    monitors: Vec<Shown>,
+}
+
+impl History {
+    pub fn load() -> Self {
+        super::load("history")
+    }
+
+    pub fn record(monitors: Vec<Shown>) -> Result<()> {
+        if CONFIG.history_length == 0 || monitors.is_empty() {
+            return Ok(());
+        }
+
+        let mut history: Self = super::try_load("history")?;
+        history.sets.push(Set { time: Timestamp::now(), monitors });
+
+        let excess = history.sets.len().saturating_sub(CONFIG.history_length);
+        history.sets.drain(..excess);
+
+        super::save("history", &history)
+    }
+
+    // Every wallpaper in the most recent sets, including the current set.
+    pub fn recent(&self, sets: usize) -> impl Iterator<Item = &OriginalWallpaperID> {
+        self.sets
+            .iter()
+            .rev()
+            .take(sets)
+            .flat_map(|set| set.monitors.iter().map(|s| &s.original))
+    }
+
+    // The wallpapers shown before the current ones. For a single monitor, this is the most recent
+    // wallpaper shown on it that differs from the one it shows now, which may have been set in an
+    // older set if only other monitors changed since.
+    #[cfg_attr(all(unix, not(feature = "x11")), allow(unused))]
+    pub fn previous(&self, monitor: Option<(usize, &Monitor)>) -> Vec<Shown> {
+        match monitor {
+            Some((n, m)) => {
+                let mut shown = self
+                    .sets
+                    .iter()
+                    .rev()
+                    .filter_map(|set| set.monitors.iter().find(|s| s.matches(n, m)));
+                let current = shown.next();
+                shown
+                    .find(|s| current.is_none_or(|c| c.original != s.original))
+                    .into_iter()
+                    .cloned()
+                    .collect()
+            }
+            None => self
+                .sets
+                .iter()
+                .rev()
+                .nth(1)
+                .map(|set| set.monitors.clone())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+pub fn print() {
+    let history = History::load();
+    if history.sets.is_empty() {
+        println!("No wallpapers have been set");
+        return;
+    }
+
+    let tz = TimeZone::system();
+    for set in history.sets.iter().rev() {
+        println!("{}", set.time.to_zoned(tz.clone()).strftime("%Y-%m-%d %H:%M:%S"));
+        for s in &set.monitors {
+            let connector = s.connector.as_deref().unwrap_or("unknown");
+            println!(
+                "  {} {connector} {}x{}: {:?}",
+                s.index,
+                s.width,
+                s.height,
+                s.original.slash_path()
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn shown(index: usize, connector: &str, original: &str) -> Shown {
+        Shown {
+            index,
+            connector: Some(connector.to_string()),
+            width: 1920,
+            height: 1080,
+            original: OriginalWallpaperID::from_rel_path(original),
+            cached: PathBuf::new(),
+            props: None,
+        }
+    }
+
+    #[test]
+    fn test_previous_monitor() {
+        let set = |monitors| Set { time: Timestamp::UNIX_EPOCH, monitors };
+        let history = History {
+            sets: vec![
+                set(vec![shown(0, "DP-1", "a.png"), shown(1, "DP-2", "b.png")]),
+                // DP-1 moved to index 1 after DP-2 was disconnected and reconnected.
+                set(vec![shown(1, "DP-1", "c.png")]),
+                // Only DP-2 changed, then DP-1 was set to the same wallpaper again.
+                set(vec![shown(0, "DP-2", "d.png")]),
+                set(vec![shown(1, "DP-1", "c.png")]),
+            ],
+        };
+
+        let mut m = Monitor::canvas(1920, 1080);
+        m.connector = Some("DP-1".to_string());
+        let previous = history.previous(Some((1, &m)));
+        assert_eq!(previous.len(), 1);
+        assert_eq!(previous[0].original, OriginalWallpaperID::from_rel_path("a.png"));
+
+        m.connector = Some("DP-3".to_string());
+        assert!(history.previous(Some((1, &m))).is_empty());
+    }
+}
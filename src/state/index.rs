@@ -0,0 +1,97 @@
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use color_eyre::Result;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::directories::ids::{OriginalWallpaperID, WallpaperID};
+
+// Values computed from originals, kept until the files are modified.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Index<T> {
+    #[serde(skip)]
+    name: &'static str,
+    #[serde(default = "BTreeMap::new")]
+    originals: BTreeMap<PathBuf, Entry<T>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Entry<T> {
+    // Nanoseconds since the epoch, entries are stale if the file has been modified since.
+    mtime: u64,
+    #[serde(flatten)]
+    value: T,
+}
+
+impl<T> Default for Index<T> {
+    fn default() -> Self {
+        Self { name: "", originals: BTreeMap::new() }
+    }
+}
+
+impl<T: Serialize + DeserializeOwned + Send> Index<T> {
+    pub fn load(name: &'static str) -> Self {
+        Self { name, ..super::load(name) }
+    }
+
+    // Wallpapers that are new or have been modified since they were indexed.
+    pub fn stale<'a>(
+        &self,
+        wallpapers: &'a [OriginalWallpaperID],
+    ) -> Vec<(&'a OriginalWallpaperID, u64)> {
+        wallpapers
+            .iter()
+            .filter_map(|w| Some((w, super::mtime(&w.original_abs_path())?)))
+            .filter(|(w, mtime)| {
+                self.originals.get(w.slash_path()).is_none_or(|e| e.mtime != *mtime)
+            })
+            .collect()
+    }
+
+    // Wallpapers that can't be read are left out, and retried next time. Returns whether anything
+    // needs to be saved.
+    pub fn update(
+        &mut self,
+        stale: Vec<(&OriginalWallpaperID, u64)>,
+        compute: impl Fn(&Path) -> Option<T> + Sync,
+    ) -> bool {
+        if stale.is_empty() {
+            return false;
+        }
+
+        let updated: Vec<_> = stale
+            .into_par_iter()
+            .filter_map(|(w, mtime)| {
+                let value = compute(&w.original_abs_path())?;
+                Some((w.slash_path().to_path_buf(), Entry { mtime, value }))
+            })
+            .collect();
+
+        self.originals.extend(updated);
+        true
+    }
+
+    pub fn get(&self, wid: &OriginalWallpaperID) -> Option<&T> {
+        self.originals.get(wid.slash_path()).map(|e| &e.value)
+    }
+
+    // Drops entries for wallpapers that no longer exist.
+    pub fn prune(&mut self, wallpapers: &[OriginalWallpaperID]) -> Result<()> {
+        let valid: HashSet<&Path> =
+            wallpapers.iter().map(OriginalWallpaperID::slash_path).collect();
+
+        let before = self.originals.len();
+        self.originals.retain(|p, _| valid.contains(p.as_path()));
+
+        if self.originals.len() == before {
+            return Ok(());
+        }
+        self.save()
+    }
+
+    pub fn save(&self) -> Result<()> {
+        super::save(self.name, self)
+    }
+}
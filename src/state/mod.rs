@@ -0,0 +1,140 @@
+use std::fs::read_to_string;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use color_eyre::Result;
+use color_eyre::eyre::WrapErr;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use tempfile::NamedTempFile;
+
+use crate::config::CONFIG;
+use crate::monitors::Monitor;
+
+pub mod bans;
+pub mod current;
+pub mod hashes;
+pub mod history;
+mod index;
+pub mod pins;
+pub mod ratings;
+pub mod resolutions;
+
+// Small TOML files stored next to the database, like "wallpaper-db.bans.toml".
+pub fn path(name: &str) -> PathBuf {
+    let mut p = CONFIG.database.clone().into_os_string();
+    p.push(".");
+    p.push(name);
+    p.push(".toml");
+    p.into()
+}
+
+// Missing files are empty, and broken files are reported and treated as empty so one bad file
+// can't take down the daemon.
+pub fn load<T: DeserializeOwned + Default>(name: &str) -> T {
+    load_from(&path(name))
+}
+
+// For commands that modify and save the file, which must not replace a broken file with an empty
+// one. Missing files are still empty.
+pub fn try_load<T: DeserializeOwned + Default>(name: &str) -> Result<T> {
+    try_load_from(&path(name))
+}
+
+fn load_from<T: DeserializeOwned + Default>(p: &Path) -> T {
+    try_load_from(p).unwrap_or_else(|e| {
+        println!("{e:#}, ignoring it");
+        T::default()
+    })
+}
+
+fn try_load_from<T: DeserializeOwned + Default>(p: &Path) -> Result<T> {
+    if !p.is_file() {
+        return Ok(T::default());
+    }
+
+    // TOML files are UTF-8 by definition
+    let s = read_to_string(p).wrap_err_with(|| format!("Error reading {p:?}"))?;
+    toml::from_str(&s).wrap_err_with(|| format!("Unable to parse {p:?}"))
+}
+
+// Written atomically so readers in other processes never see partial files.
+pub fn save<T: Serialize>(name: &str, value: &T) -> Result<()> {
+    save_to(&path(name), value)
+}
+
+fn save_to<T: Serialize>(p: &Path, value: &T) -> Result<()> {
+    let out = toml::to_string(value)?;
+
+    let mut tmp =
+        NamedTempFile::new_in(p.parent().expect("Database must have a parent directory"))?;
+    tmp.write_all(out.as_bytes())?;
+    tmp.persist(p)?;
+    Ok(())
+}
+
+// Monitors are identified by connector when possible, since indices can shift when monitors are
+// connected or disconnected.
+pub fn same_monitor(index: usize, connector: &Option<String>, i: usize, m: &Monitor) -> bool {
+    match (connector, &m.connector) {
+        (Some(a), Some(b)) => a == b,
+        _ => index == i,
+    }
+}
+
+// Nanoseconds since the epoch, for invalidating entries in indices of files.
+fn mtime(p: &Path) -> Option<u64> {
+    let modified = p.metadata().ok()?.modified().ok()?;
+    modified.duration_since(UNIX_EPOCH).ok()?.as_nanos().try_into().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::write;
+
+    use super::*;
+    use crate::directories::ids::OriginalWallpaperID;
+    use crate::state::current::{Current, Shown};
+
+    #[test]
+    fn test_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let p = dir.path().join("db.current.toml");
+
+        let current = Current {
+            monitors: vec![Shown {
+                index: 1,
+                connector: Some("DP-1".to_string()),
+                width: 2560,
+                height: 1440,
+                original: OriginalWallpaperID::from_rel_path("a/b.png"),
+                cached: PathBuf::from("/cache/2560x1440/a/b.png"),
+                props: None,
+            }],
+        };
+        save_to(&p, &current).unwrap();
+
+        let loaded: Current = load_from(&p);
+        assert_eq!(loaded.monitors.len(), 1);
+        let s = &loaded.monitors[0];
+        assert_eq!((s.index, s.connector.as_deref()), (1, Some("DP-1")));
+        assert_eq!((s.width, s.height), (2560, 1440));
+        assert_eq!(s.original, current.monitors[0].original);
+        assert_eq!(s.cached, current.monitors[0].cached);
+    }
+
+    #[test]
+    fn test_broken_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let p = dir.path().join("db.current.toml");
+
+        let missing: Current = load_from(&p);
+        assert!(missing.monitors.is_empty());
+
+        write(&p, "monitors = [{ index = ").unwrap();
+        let broken: Current = load_from(&p);
+        assert!(broken.monitors.is_empty());
+        assert!(try_load_from::<Current>(&p).is_err());
+    }
+}
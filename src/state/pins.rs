@@ -0,0 +1,86 @@
+use std::path::Path;
+
+use color_eyre::Result;
+use color_eyre::eyre::bail;
+use serde::{Deserialize, Serialize};
+
+use super::same_monitor;
+use crate::directories::ids::{OriginalWallpaperID, WallpaperID};
+use crate::directories::resolve_original;
+use crate::monitors::{self, Monitor};
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Pins {
+    #[serde(default)]
+    pins: Vec<Pin>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Pin {
+    index: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    connector: Option<String>,
+    original: OriginalWallpaperID,
+}
+
+impl Pins {
+    pub fn load() -> Self {
+        super::load("pins")
+    }
+
+    // The wallpaper pinned to this monitor, if it still exists.
+    pub fn get(&self, index: usize, m: &Monitor) -> Option<&OriginalWallpaperID> {
+        self.pins
+            .iter()
+            .find(|p| same_monitor(p.index, &p.connector, index, m))
+            .map(|p| &p.original)
+            .filter(|wid| wid.original_abs_path().is_file())
+    }
+}
+
+pub async fn pin(monitor: usize, file: &Path) -> Result<()> {
+    let monitors = monitors::init().list_monitors().await?;
+    pin_to(&monitors, monitor, file)
+}
+
+// For when the monitors are already known, like in the daemon.
+pub fn pin_to(monitors: &[Monitor], monitor: usize, file: &Path) -> Result<()> {
+    let Some(m) = monitors.get(monitor) else {
+        bail!("No monitor {monitor}, see list-monitors");
+    };
+
+    let Some(wid) = resolve_original(file) else {
+        bail!("{file:?} is not a wallpaper in the originals directory");
+    };
+
+    let mut pins: Pins = super::try_load("pins")?;
+    pins.pins.retain(|p| !same_monitor(p.index, &p.connector, monitor, m));
+    println!("Pinned {:?} to monitor {monitor} {m:?}", wid.slash_path());
+    pins.pins.push(Pin {
+        index: monitor,
+        connector: m.connector.clone(),
+        original: wid,
+    });
+
+    super::save("pins", &pins)
+}
+
+pub async fn unpin(monitor: usize) -> Result<()> {
+    let monitors = monitors::init().list_monitors().await?;
+
+    let mut pins: Pins = super::try_load("pins")?;
+    let before = pins.pins.len();
+    // Disconnected monitors can still be unpinned by index
+    pins.pins.retain(|p| match monitors.get(monitor) {
+        Some(m) => !same_monitor(p.index, &p.connector, monitor, m),
+        None => p.index != monitor,
+    });
+
+    if pins.pins.len() == before {
+        println!("Monitor {monitor} was not pinned");
+        return Ok(());
+    }
+
+    println!("Unpinned monitor {monitor}");
+    super::save("pins", &pins)
+}
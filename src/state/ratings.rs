@@ -0,0 +1,75 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use color_eyre::Result;
+use serde::{Deserialize, Serialize};
+
+use super::current::Current;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Ratings {
+    // Forward slash separated paths relative to the originals directory, rated from 1 to 5.
+    #[serde(default)]
+    ratings: BTreeMap<PathBuf, u8>,
+}
+
+impl Ratings {
+    // Ratings outside 1 to 5, from editing the file by hand, are reported and ignored.
+    pub fn load() -> Self {
+        Self::valid(super::load("ratings"))
+    }
+
+    fn valid(mut ratings: Self) -> Self {
+        ratings.ratings.retain(|p, r| {
+            let valid = (1..=5).contains(r);
+            if !valid {
+                println!("Ignoring invalid rating {r} for {p:?}");
+            }
+            valid
+        });
+        ratings
+    }
+
+    pub fn get(&self, slash_path: &Path) -> Option<u8> {
+        self.ratings.get(slash_path).copied()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ratings.is_empty()
+    }
+}
+
+// Rates, or clears the rating of, the wallpapers currently shown on every monitor or only the
+// given monitor.
+pub fn rate(rating: Option<u8>, monitor: Option<usize>) -> Result<()> {
+    let current = Current::load();
+    let shown: Vec<_> = current
+        .monitors
+        .iter()
+        .filter(|s| monitor.is_none_or(|m| m == s.index))
+        .collect();
+
+    if shown.is_empty() {
+        match monitor {
+            Some(m) => println!("No wallpaper has been set on monitor {m}"),
+            None => println!("No wallpapers have been set"),
+        }
+        return Ok(());
+    }
+
+    let mut ratings = Ratings::valid(super::try_load("ratings")?);
+    for s in shown {
+        match rating {
+            Some(r) => {
+                ratings.ratings.insert(s.original.slash_path().to_path_buf(), r);
+                println!("Rated {:?} {r}/5", s.original.slash_path());
+            }
+            None => {
+                ratings.ratings.remove(s.original.slash_path());
+                println!("Cleared rating for {:?}", s.original.slash_path());
+            }
+        }
+    }
+
+    super::save("ratings", &ratings)
+}
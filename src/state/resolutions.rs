@@ -0,0 +1,47 @@
+use color_eyre::Result;
+use serde::{Deserialize, Serialize};
+
+use super::index::Index;
+use crate::directories::ids::OriginalWallpaperID;
+use crate::wallpaper::Res;
+
+// Resolutions of originals, so selection doesn't need to read every image header.
+pub struct Resolutions(Index<Dimensions>);
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Dimensions {
+    width: u32,
+    height: u32,
+}
+
+impl Resolutions {
+    // Loads the index, reading the resolutions of any new or modified wallpapers. Dry runs don't
+    // save them.
+    pub fn load(wallpapers: &[OriginalWallpaperID], save: bool) -> Result<Self> {
+        let mut index = Index::load("resolutions");
+
+        let stale = index.stale(wallpapers);
+        let updated = index.update(stale, |p| {
+            let (width, height) = image::image_dimensions(p).ok()?;
+            Some(Dimensions { width, height })
+        });
+        if updated && save {
+            index.save()?;
+        }
+
+        Ok(Self(index))
+    }
+
+    pub fn get(&self, wid: &OriginalWallpaperID) -> Option<Res> {
+        self.0.get(wid).map(|d| (d.width, d.height).into())
+    }
+
+    #[cfg(test)]
+    pub fn parse(s: &str) -> Self {
+        Self(toml::from_str(s).unwrap())
+    }
+
+    pub fn prune(&mut self, wallpapers: &[OriginalWallpaperID]) -> Result<()> {
+        self.0.prune(wallpapers)
+    }
+}
@@ -22,7 +22,9 @@ use crate::processing::resample::FilterType::Lanczos3;
 #[cfg(feature = "opencl")]
 use crate::processing::resample::resize_opencl;
 use crate::processing::resample::resize_par_linear;
-use crate::processing::{UPSCALING, WORKER};
+use crate::processing::{BACKGROUND, UPSCALING, WORKER};
+
+pub mod span;
 
 // This is a small cache because the files can get very large.
 // For interactive or preview this is sufficient.
@@ -83,8 +85,38 @@ impl Res {
 
         NonZeroU8::new(scale).unwrap()
     }
+
+    // How much this image needs to be scaled to cover the monitor, after cropping or padding.
+    pub fn upscale(self, props: &Option<ImageProperties>, m: &Monitor) -> f64 {
+        let r = if let Some(props) = props { self.apply_crop_pad(props) } else { self };
+
+        if r.is_empty() {
+            return f64::INFINITY;
+        }
+
+        f64::max(m.width as f64 / r.w as f64, m.height as f64 / r.h as f64)
+    }
+
+    // How badly this image fits the monitor. The fraction of the scaled image that will be
+    // cropped off, plus a penalty for upscaling that reaches 1.0 at the 32x cap.
+    pub fn fit_cost(self, props: &Option<ImageProperties>, m: &Monitor) -> f64 {
+        let r = if let Some(props) = props { self.apply_crop_pad(props) } else { self };
+
+        if r.is_empty() || m.width == 0 || m.height == 0 {
+            return UNFIT_COST;
+        }
+
+        let scale = r.upscale(&None, m);
+        let visible =
+            (m.width as f64 * m.height as f64) / (r.w as f64 * r.h as f64 * scale * scale);
+
+        (1.0 - visible) + f64::max(scale.log2(), 0.0) / 5.0
+    }
 }
 
+// Worse than any image that can actually be displayed.
+pub const UNFIT_COST: f64 = 100.0;
+
 #[derive(Debug)]
 enum IntermediateFile {
     AlreadyExists(PathBuf),
@@ -115,6 +147,8 @@ pub struct Wallpaper<'a, T: WallpaperID> {
     pub id: &'a T,
     monitors: &'a [Monitor],
     parent_tdir: &'a LazyLock<TempDir>,
+    // Processed on the background pool
+    background: bool,
     tdir: OnceLock<TempDir>,
     // This could save time and memory during interactive mode, but likely not worth too much.
     // original_image: OnceCell<Arc<DynamicImage>>,
@@ -132,6 +166,7 @@ impl<'a, T: WallpaperID> Wallpaper<'a, T> {
             id,
             monitors,
             parent_tdir,
+            background: false,
             tdir: OnceLock::new(),
             resolution: OnceLock::new(),
             mtime: OnceLock::new(),
@@ -140,6 +175,11 @@ impl<'a, T: WallpaperID> Wallpaper<'a, T> {
 }
 
 impl<T: WallpaperID> Wallpaper<'_, T> {
+    pub const fn in_background(mut self) -> Self {
+        self.background = true;
+        self
+    }
+
     pub fn process(&self, compress: bool) {
         let uncached_monitors = self.get_uncached_files();
 
@@ -152,7 +192,13 @@ impl<T: WallpaperID> Wallpaper<'_, T> {
             return;
         }
 
-        WORKER.in_place_scope_fifo(|s| {
+        let (worker, upscaling) = if self.background {
+            (&*BACKGROUND, &*BACKGROUND)
+        } else {
+            (&*WORKER, &*UPSCALING)
+        };
+
+        worker.in_place_scope_fifo(|s| {
             uncached_monitors
                 .iter()
                 .filter(|uf| matches!(uf.cropped, Some(IntermediateFile::MustBeWritten(_))))
@@ -167,7 +213,7 @@ impl<T: WallpaperID> Wallpaper<'_, T> {
             return;
         }
 
-        UPSCALING.in_place_scope_fifo(|s| {
+        upscaling.in_place_scope_fifo(|s| {
             uncached_monitors
                 .iter()
                 .filter(|uf| matches!(uf.scaled, IntermediateFile::MustBeWritten(_)))
@@ -179,7 +225,7 @@ impl<T: WallpaperID> Wallpaper<'_, T> {
         }
 
         // Guaranteed to be work here, so we can go straight to the pool
-        WORKER.scope_fifo(|s| {
+        worker.scope_fifo(|s| {
             uncached_monitors
                 .iter()
                 .for_each(|uf| s.spawn_fifo(move |_s| Self::finish(uf, compress)))
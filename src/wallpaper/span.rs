@@ -0,0 +1,241 @@
+use std::fs::{File, create_dir_all};
+use std::path::PathBuf;
+use std::slice;
+use std::sync::LazyLock;
+
+use image::codecs::png::{CompressionType, FilterType, PngEncoder};
+use image::{ImageEncoder, imageops};
+use tempfile::TempDir;
+
+use super::{Wallpaper, get_mtime};
+use crate::closing;
+use crate::config::{CONFIG, Span};
+use crate::directories::ids::{OriginalWallpaperID, WallpaperID};
+use crate::monitors::Monitor;
+use crate::selection::pools::glob_matches;
+
+#[derive(Debug)]
+pub struct Layout {
+    // Covers every monitor in the span, including the space hidden by bezels.
+    pub canvas: Monitor,
+    pub slices: Vec<Slice>,
+}
+
+#[derive(Debug)]
+pub struct Slice {
+    // Index into the list of monitors
+    pub index: usize,
+    // Offset inside the canvas
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+impl Layout {
+    pub fn contains(&self, index: usize) -> bool {
+        self.slices.iter().any(|s| s.index == index)
+    }
+
+    fn canvas_path(&self, wid: &OriginalWallpaperID) -> PathBuf {
+        wid.cached_abs_path(&self.canvas, &wid.get_props(&self.canvas))
+    }
+
+    // Slices are cached alongside normal wallpapers for the same monitor, in a directory specific
+    // to this layout.
+    pub fn slice_path(&self, wid: &OriginalWallpaperID, index: usize) -> Option<PathBuf> {
+        let s = self.slices.iter().find(|s| s.index == index)?;
+
+        let canvas_path = self.canvas_path(wid);
+        let rel = canvas_path
+            .strip_prefix(self.canvas.cache_dir())
+            .expect("Cached file was not in cache directory");
+
+        let (w, h) = (self.canvas.width, self.canvas.height);
+        let cache_dir = Monitor::canvas(s.width, s.height).cache_dir();
+        Some(cache_dir.join(format!("span-{w}x{h}+{}+{}", s.x, s.y)).join(rel))
+    }
+
+    pub fn paths(&self, wid: &OriginalWallpaperID) -> Vec<PathBuf> {
+        let mut paths: Vec<_> =
+            self.slices.iter().filter_map(|s| self.slice_path(wid, s.index)).collect();
+        paths.push(self.canvas_path(wid));
+        paths
+    }
+}
+
+pub fn included(wid: &OriginalWallpaperID) -> bool {
+    CONFIG.spans.iter().any(|s| glob_matches(&s.include, wid))
+}
+
+fn layout(span: &Span, monitors: &[Monitor]) -> Option<Layout> {
+    let members = span
+        .monitors
+        .iter()
+        .map(|c| monitors.iter().enumerate().find(|(_, m)| m.connector.as_ref() == Some(c)))
+        .collect::<Option<Vec<_>>>()?;
+
+    let sizes: Vec<_> = members.iter().map(|(_, m)| (m.width, m.height)).collect();
+    let positions = members.iter().map(|(_, m)| m.position()).collect::<Option<Vec<_>>>();
+    let ((width, height), offsets) = arrange(&sizes, positions, span.bezel)?;
+
+    let slices = members
+        .iter()
+        .zip(offsets)
+        .map(|((index, m), (x, y))| Slice {
+            index: *index,
+            x,
+            y,
+            width: m.width,
+            height: m.height,
+        })
+        .collect();
+
+    Some(Layout { canvas: Monitor::canvas(width, height), slices })
+}
+
+// The size of the canvas and the offset of each monitor inside it.
+type Arrangement = ((u32, u32), Vec<(u32, u32)>);
+
+fn arrange(
+    sizes: &[(u32, u32)],
+    positions: Option<Vec<(i32, i32)>>,
+    bezel: u32,
+) -> Option<Arrangement> {
+    // Without known positions, lay the monitors out left to right in the configured order.
+    let positions: Vec<(i64, i64)> = match positions {
+        Some(positions) => positions.into_iter().map(|(x, y)| (x.into(), y.into())).collect(),
+        None => sizes
+            .iter()
+            .scan(0, |x, (w, _)| {
+                let left = *x;
+                *x += i64::from(*w);
+                Some((left, 0))
+            })
+            .collect(),
+    };
+
+    // Push each monitor away by one bezel for every monitor entirely to its left or above it.
+    let bezel = i64::from(bezel);
+    let shifted: Vec<_> = positions
+        .iter()
+        .map(|(x, y)| {
+            let others = positions.iter().zip(sizes);
+            let columns =
+                others.clone().filter(|((ox, _), (w, _))| ox + i64::from(*w) <= *x).count();
+            let rows = others.filter(|((_, oy), (_, h))| oy + i64::from(*h) <= *y).count();
+            (x + columns as i64 * bezel, y + rows as i64 * bezel)
+        })
+        .collect();
+
+    let min_x = shifted.iter().map(|p| p.0).min()?;
+    let min_y = shifted.iter().map(|p| p.1).min()?;
+    let max_x = shifted.iter().zip(sizes).map(|(p, (w, _))| p.0 + i64::from(*w)).max()?;
+    let max_y = shifted.iter().zip(sizes).map(|(p, (_, h))| p.1 + i64::from(*h)).max()?;
+
+    let offsets = shifted.iter().map(|(x, y)| ((x - min_x) as u32, (y - min_y) as u32)).collect();
+    Some((((max_x - min_x) as u32, (max_y - min_y) as u32), offsets))
+}
+
+// Layouts for every configured span that is fully connected, paired with the span itself.
+// Monitors are only used by the first span containing them.
+pub fn layouts(monitors: &[Monitor]) -> Vec<(&'static Span, Layout)> {
+    let mut layouts: Vec<(&Span, Layout)> = Vec::new();
+
+    for span in &CONFIG.spans {
+        if let Some(l) = layout(span, monitors)
+            && !layouts.iter().any(|(_, o)| l.slices.iter().any(|s| o.contains(s.index)))
+        {
+            layouts.push((span, l));
+        }
+    }
+
+    layouts
+}
+
+// Spans where every monitor was given the same spanning wallpaper.
+pub fn active(
+    selection: &[Option<OriginalWallpaperID>],
+    monitors: &[Monitor],
+) -> Vec<(OriginalWallpaperID, Layout)> {
+    layouts(monitors)
+        .into_iter()
+        .filter_map(|(span, l)| {
+            let wid = selection[l.slices[0].index].as_ref()?;
+            let all_same = l.slices.iter().all(|s| selection[s.index].as_ref() == Some(wid));
+
+            (all_same && glob_matches(&span.include, wid)).then(|| (wid.clone(), l))
+        })
+        .collect()
+}
+
+// Processes the wallpaper for the whole canvas, then slices it for each monitor.
+pub fn process(
+    wid: &OriginalWallpaperID,
+    layout: &Layout,
+    tdir: &LazyLock<TempDir>,
+    background: bool,
+) {
+    let mut canvas = Wallpaper::new(wid, slice::from_ref(&layout.canvas), tdir);
+    canvas.background = background;
+    canvas.process(true);
+
+    let canvas_path = layout.canvas_path(wid);
+    if closing::closed() || !canvas_path.is_file() {
+        return;
+    }
+
+    let mtime = get_mtime(&canvas_path);
+    let uncached: Vec<_> = layout
+        .slices
+        .iter()
+        .filter_map(|s| Some((s, layout.slice_path(wid, s.index)?)))
+        .filter(|(_, p)| !p.is_file() || get_mtime(p) < mtime)
+        .collect();
+
+    if uncached.is_empty() {
+        return;
+    }
+
+    let img = image::open(&canvas_path)
+        .unwrap_or_else(|e| panic!("Unable to read image {canvas_path:?}: {e}"))
+        .into_rgb8();
+
+    for (s, path) in uncached {
+        let sliced = imageops::crop_imm(&img, s.x, s.y, s.width, s.height).to_image();
+
+        create_dir_all(path.parent().expect("Impossible for cached file to have no directory"))
+            .expect("Unable to create cache directories");
+
+        let f = File::create(&path).expect("Couldn't create output file");
+        let enc = PngEncoder::new_with_quality(f, CompressionType::Best, FilterType::NoFilter);
+
+        enc.write_image(&sliced, sliced.width(), sliced.height(), image::ColorType::Rgb8.into())
+            .unwrap_or_else(|e| panic!("Failed to save file {path:?}: {e}"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arrange_left_to_right() {
+        let sizes = [(1920, 1080), (2560, 1440)];
+        assert_eq!(arrange(&sizes, None, 0), Some(((4480, 1440), vec![(0, 0), (1920, 0)])));
+        assert_eq!(arrange(&sizes, None, 40), Some(((4520, 1440), vec![(0, 0), (1960, 0)])));
+    }
+
+    #[test]
+    fn test_arrange_positions() {
+        // A monitor above two side by side ones, placed at negative coordinates.
+        let sizes = [(1920, 1080), (1920, 1080), (1920, 1080)];
+        let positions = Some(vec![(-1920, 0), (0, 0), (-960, -1080)]);
+        assert_eq!(
+            arrange(&sizes, positions, 20),
+            Some(((3860, 2180), vec![(0, 1100), (1940, 1100), (960, 0)]))
+        );
+
+        assert_eq!(arrange(&[], Some(Vec::new()), 20), None);
+    }
+}